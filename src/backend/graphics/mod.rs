@@ -25,7 +25,8 @@ use crate::world::entity::{EntityContainer, EntityIterator};
 use crate::world::entity::component::transform::Transform;
 use crate::world::entity::component::model::GraphicsModel;
 use crate::world::entity::component::{Component, ComponentManager};
-use std::collections::HashMap;
+use crate::world::entity::registry::EntityId;
+use std::collections::{HashMap, BTreeMap};
 use wgpu::{BufferAddress, BindGroupLayout, BufferDescriptor, CommandEncoder, RenderPass, Device, Buffer, BindGroup, TextureView};
 use wgpu::util::DeviceExt;
 
@@ -258,9 +259,10 @@ impl WGPUState {
         );
     }
 
-    pub fn render(&mut self, graphics_cache: &HashMap<&'static str, Vec<Arc<ModelView>>>, 
+    pub fn render(&mut self, graphics_cache: &HashMap<&'static str, BTreeMap<EntityId, Arc<ModelView>>>, 
                              loaded_models: &HashMap<&'static str, Model>,
-                             fps: u128) {
+                             fps: u128,
+                             system_status: &[String]) {
         
         let optional_frame = self.swap_chain.get_current_frame();
         if optional_frame.is_err() { return; }
@@ -301,7 +303,7 @@ impl WGPUState {
             for model_str in graphics_cache.keys() {
                 let model = loaded_models.get(model_str).unwrap();
 
-                for model_view in graphics_cache.get(model_str).unwrap() {
+                for model_view in graphics_cache.get(model_str).unwrap().values() {
                     model_view.write_to_gpu();
                     render_pass.draw_model(model, &self.uniform_bind_group, &model_view.bind_group);
                 }
@@ -314,7 +316,17 @@ impl WGPUState {
                               &frame.output.view,
                               &self.sc_desc,
                               format!("FPS: {}", fps),
-                              (self.sc_desc.width as f32 - 200f32, 0.0));
+                              (self.sc_desc.width as f32 - 200f32, 0.0),
+                              50.0);
+
+        ui::text::render_text(&self.device, 
+                              &self.queue,
+                              &mut encoder,
+                              &frame.output.view,
+                              &self.sc_desc,
+                              system_status.join("\n"),
+                              (10.0, 10.0),
+                              20.0);
 
         self.queue.submit(iter::once(encoder.finish()));
     }
@@ -7,7 +7,8 @@ pub fn render_text<'a>(device: &Device,
                    view: &TextureView, 
                    sc_desc: &SwapChainDescriptor,
                    text: String, 
-                   screen_position: (f32, f32)) {
+                   screen_position: (f32, f32),
+                   scale: f32) {
 
     let font = ab_glyph::FontArc::try_from_slice(include_bytes!("../../../../res/font/Inconsolata-Bold.ttf"))
         .expect("Load font");
@@ -19,7 +20,7 @@ pub fn render_text<'a>(device: &Device,
         screen_position,
         text: vec![Text::new(text.as_str())
             .with_color([1.0, 1.0, 1.0, 1.0])
-            .with_scale(50.0)],
+            .with_scale(scale)],
         ..Section::default()
     };
 
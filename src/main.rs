@@ -21,18 +21,28 @@ use winit::{
     window::{Window, WindowBuilder},
 };
 use futures::executor::block_on;
+use rand_core::SeedableRng;
+use std::time::{Duration, Instant};
 use crate::state::State;
+use crate::world::World;
+use crate::world::entity::prefab::rand_tile::RandomTile;
+use crate::world::system::scheduler::SimulationTime;
 
 fn main() {
     env_logger::init();
 
+    if std::env::args().any(|arg| arg == "--headless") {
+        run_headless(world_generator());
+        return;
+    }
+
     let event_loop = EventLoop::new();
     let window = WindowBuilder::new()
         .build(&event_loop)
         .unwrap();
 
     // Since main can't be async, we're going to need to block
-    let mut state = block_on(State::new(&window, false));
+    let mut state = block_on(State::new(&window, false, world_generator()));
     state.update_graphics_data();
 
     let mut window_focused = false;
@@ -62,6 +72,39 @@ fn main() {
                                 virtual_keycode: Some(VirtualKeyCode::Escape),
                                 ..
                             } => state.set_escape_status(&window, true),
+                            KeyboardInput {
+                                state: ElementState::Pressed,
+                                virtual_keycode: Some(VirtualKeyCode::P),
+                                ..
+                            } => state.toggle_pause(),
+                            KeyboardInput {
+                                state: ElementState::Pressed,
+                                virtual_keycode: Some(VirtualKeyCode::N),
+                                ..
+                            } => state.step_simulation(1),
+                            KeyboardInput {
+                                state: ElementState::Pressed,
+                                virtual_keycode: Some(VirtualKeyCode::F12),
+                                ..
+                            } => state.export_trace(),
+                            KeyboardInput {
+                                state: ElementState::Pressed,
+                                virtual_keycode: Some(VirtualKeyCode::Equals),
+                                ..
+                            } | KeyboardInput {
+                                state: ElementState::Pressed,
+                                virtual_keycode: Some(VirtualKeyCode::NumpadAdd),
+                                ..
+                            } => state.scale_time(2.0),
+                            KeyboardInput {
+                                state: ElementState::Pressed,
+                                virtual_keycode: Some(VirtualKeyCode::Minus),
+                                ..
+                            } | KeyboardInput {
+                                state: ElementState::Pressed,
+                                virtual_keycode: Some(VirtualKeyCode::NumpadSubtract),
+                                ..
+                            } => state.scale_time(0.5),
                             _ => {}
                         }
                     },
@@ -95,6 +138,9 @@ fn main() {
             Event::DeviceEvent{event, ..} if window_focused => {
                 state.input(&event, &window);
             },
+            Event::LoopDestroyed => {
+                state.shutdown();
+            },
             Event::MainEventsCleared => {
                 // RedrawRequested will only trigger once, unless we manually
                 // request it.
@@ -105,6 +151,75 @@ fn main() {
     });
 }
 
+const USAGE: &str = "Usage: [--seed <n>] [--headless [--ticks <n>] [--seconds <n>]]";
+
+// The number following the given flag on the command line, e.g. `--seed 42`. A flag that isn't
+// followed by a number is a usage error, which ends the program:
+fn numeric_argument(flag: &str) -> Option<u64> {
+    let args: Vec<String> = std::env::args().collect();
+    let i = args.iter().position(|arg| arg == flag)?;
+
+    match args.get(i + 1) {
+        Some(value) => match value.parse::<u64>() {
+            Ok(number) => Some(number),
+            Err(_) => exit_with_usage(&format!("{} has to be followed by a number, not '{}'", flag, value))
+        },
+        None => exit_with_usage(&format!("{} has to be followed by a number", flag))
+    }
+}
+
+fn exit_with_usage(problem: &str) -> ! {
+    error!("{}", problem);
+    error!("{}", USAGE);
+    std::process::exit(2)
+}
+
+// The same seed, given as `--seed <n>`, generates the same world and simulates it the same way.
+// Without one, the world is different every time:
+fn world_generator() -> RandomTile {
+    match numeric_argument("--seed") {
+        Some(seed) => RandomTile::seed_from_u64(seed),
+        None => RandomTile::from_entropy()
+    }
+}
+
+// Simulates the world without a window or graphics backend, reporting on the systems every second.
+// Runs until at least `--ticks <n>` ticks have run or `--seconds <n>` seconds have passed, if
+// either is given, and shuts the world down cleanly then:
+fn run_headless(world_generator: RandomTile) {
+    let (max_ticks, max_duration) = (numeric_argument("--ticks"), numeric_argument("--seconds").map(Duration::from_secs));
+
+    let world = World::new(world_generator).expect("Couldn't schedule systems!");
+    world.spawn_simulation_thread();
+
+    let start = Instant::now();
+    let mut last_report = start;
+
+    loop {
+        std::thread::sleep(world.timestep());
+
+        let tick = world.resource::<SimulationTime>().map_or(0, |time| time.lock_resource_for_read().tick);
+        let done = max_ticks.map_or(false, |max_ticks| tick >= max_ticks) ||
+            max_duration.map_or(false, |max_duration| start.elapsed() >= max_duration);
+
+        if done || last_report.elapsed() >= Duration::from_secs(1) {
+            last_report = Instant::now();
+
+            for status in world.system_status() {
+                println!("{}", status);
+            }
+        }
+
+        if done {
+            break;
+        }
+    }
+
+    if let Err(e) = world.shutdown(Duration::from_secs(1)) {
+        error!("{}", e);
+    }
+}
+
 fn handle_window_selection_change(window: &Window, focused: bool) {
 
 }
\ No newline at end of file
@@ -1,4 +1,4 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, BTreeMap};
 use std::time::{Instant, Duration};
 use std::sync::Arc;
 
@@ -12,18 +12,25 @@ use crate::world::entity::EntityContainer;
 use crate::world::entity::component::model::GraphicsModel;
 use winit::event::DeviceEvent;
 use crate::backend::graphics::camera::Camera;
-use crate::world::entity::component::camera::Camera as CameraComponent;
+use crate::world::entity::component::camera::{Camera as CameraComponent, ActiveCamera};
+use crate::world::entity::query::QueryItem;
 use crate::world::entity::component::controller::Controller;
-use rand_core::SeedableRng;
 use crate::world::entity::prefab::rand_tile::RandomTile;
 use cgmath::Vector3;
 use crate::backend::BackendProxy;
 use crate::backend::graphics::model_view::ModelView;
+use crate::world::entity::registry::EntityId;
+use crate::world::entity::event::{Subscription, WorldEvent};
+use crate::world::system::members::MemberList;
+use crate::world::system::snapshot::TransformSnapshots;
+use crate::world::system::scheduler::SimulationClock;
 
 pub struct State {
     backend_proxy: BackendProxy,
     graphics_backend: WGPUState,
-    graphics_cache: HashMap<&'static str, Vec<Arc<ModelView>>>,
+    graphics_cache: HashMap<&'static str, BTreeMap<EntityId, Arc<ModelView>>>,
+    graphics_members: MemberList<&'static GraphicsModel>,
+    world_events: Subscription,
     world: World,
     camera: Camera,
     loaded_models: HashMap<&'static str, Model>,
@@ -32,17 +39,15 @@ pub struct State {
 }
 
 impl State {
-    pub async fn new(window: &Window, vsync: bool) -> Self {
+    pub async fn new(window: &Window, vsync: bool, world_generator: RandomTile) -> Self {
         let graphics_backend = WGPUState::new(window, vsync).await;
         let backend_proxy = BackendProxy::new(graphics_backend.device.clone(), graphics_backend.queue.clone());
         
-        let world_generator = RandomTile::from_entropy();
-        let world = World::new(world_generator, &backend_proxy);
-        let (camera_component, target) = world.query_entities(true)
-            .map(|entity| (entity.component::<CameraComponent>(), entity.component::<Transform>()))
-            .filter(|(a,b)| a.is_some() && b.is_some())
-            .map(|(a,b)| (a.unwrap(), b.unwrap()))
-            .next()
+        let world = World::new(world_generator).expect("Couldn't schedule systems!");
+        let (camera_component, target) = world.resource::<ActiveCamera>().map(|camera| camera.lock_resource_for_read().0)
+            .and_then(|id| world.entity(id))
+            .and_then(|entity| QueryItem::<(&CameraComponent, &Transform)>::of(&entity))
+            .map(|item| item.managers().clone())
             .expect("No suitable entity camera!");
 
         let camera = Camera::new(camera_component, target);
@@ -51,6 +56,8 @@ impl State {
             backend_proxy,
             graphics_backend,
             graphics_cache: HashMap::new(),
+            graphics_members: MemberList::new(),
+            world_events: world.subscribe(),
             world: world.clone(),
             camera,
             loaded_models: HashMap::new(),
@@ -64,82 +71,193 @@ impl State {
 
     // TODO: Use HashMap<&str, Vec<TransformSink>>
     pub fn update_graphics_data(&mut self) {
-        let mut drawables = self.world.query_entities(true)
-            .map(|x| x.component::<GraphicsModel>())
-            .filter(|x| x.is_some())
-            .map(|x| x.unwrap());
-
+        self.graphics_members.fetch(&self.world);
         self.graphics_cache.clear();
 
-        while let Some(ref graphics_model) = drawables.next() {
-            let obj_path = graphics_model
-                .peek(|graphics_model| graphics_model.path_to_obj)
-                .expect("Graphics model: Couldn't retrieve model path!");
-
-            let model_view = graphics_model
-                .peek(|graphics_model| graphics_model.view.clone())
-                .expect("Graphics model: Couldn't retrieve model view!");
-
-            if !self.loaded_models.contains_key(obj_path) {
-                self.loaded_models.insert(
-                    obj_path,
-                    Model::load(
-                        &self.graphics_backend.device,
-                        &self.graphics_backend.queue,
-                        &self.graphics_backend.texture_bind_group_layout,
-                        obj_path
-                    ).ok().unwrap()
-                );
-            }
+        let drawables: Vec<EntityId> = self.graphics_members
+            .iter()
+            .map(|item| item.entity.id())
+            .collect();
 
+        for id in drawables {
+            self.sync_graphics_data(id);
+        }
+    }
 
-            if !self.graphics_cache.contains_key(obj_path) {
-                self.graphics_cache.insert(obj_path, Vec::new());
-            }
+    // Patches the graphics cache for everything a world event may have affected:
+    fn apply_world_event(&mut self, event: &WorldEvent) {
+        self.graphics_members.apply(event);
 
-            let mut current = self.graphics_cache.remove(obj_path).unwrap();
-            current.push(model_view);
+        let affected: Vec<EntityId> = match *event {
+            WorldEvent::EntityEnabled(id, _) => self.world.entity(id)
+                .map(|entity| entity.query_all_entities(true).map(|entity| entity.id()).collect())
+                .unwrap_or_default(),
+            _ => vec![event.entity()]
+        };
 
-            self.graphics_cache.insert(obj_path, current);
+        for id in affected {
+            self.sync_graphics_data(id);
         }
     }
 
+    // Views are created on the GPU the first time an entity is drawn and kept for as long as it
+    // stays drawable:
+    fn sync_graphics_data(&mut self, id: EntityId) {
+        let mut model_view = None;
+        for model_views in self.graphics_cache.values_mut() {
+            model_view = model_view.or(model_views.remove(&id));
+        }
+
+        let graphics_model = match self.graphics_members.get(id) {
+            Some(item) => item.managers().clone(),
+            None => return
+        };
+
+        if !graphics_model.enabled() {
+            return;
+        }
+
+        let obj_path = graphics_model
+            .peek(|graphics_model| graphics_model.path_to_obj)
+            .expect("Graphics model: Couldn't retrieve model path!");
+
+        let backend_proxy = &self.backend_proxy;
+        let model_view = model_view.unwrap_or_else(|| Arc::new(backend_proxy.instantiate_model_view()));
+
+        if !self.loaded_models.contains_key(obj_path) {
+            self.loaded_models.insert(
+                obj_path,
+                Model::load(
+                    &self.graphics_backend.device,
+                    &self.graphics_backend.queue,
+                    &self.graphics_backend.texture_bind_group_layout,
+                    obj_path
+                ).ok().unwrap()
+            );
+        }
+
+        self.graphics_cache
+            .entry(obj_path)
+            .or_insert_with(BTreeMap::new)
+            .insert(id, model_view);
+    }
+
     pub fn resize(&mut self, new_size: winit::dpi::PhysicalSize<u32>, window: &Window){
         self.graphics_backend.resize(new_size);
         self.camera.resize(new_size, window);
     }
 
+    // Input and rendering lock components and resources outside of any system's access. They
+    // run on the thread that updates the world, so they never overlap a tick, and take one lock
+    // at a time, so a simulation thread can at most keep them waiting.
     pub fn input(&mut self, event: &DeviceEvent, window: &Window) -> bool {
         if self.camera.process_events(event, &window) { return true; }
-        else if self.world.query_entities(true)
-                          .map(|entity| entity.component::<Controller>())
-                          .filter(|controller| controller.is_some())
-                          .map(|controller| controller.unwrap())
-                          .map(|component| {
-                            let mut controller = component.lock_component_for_write();
-                            controller.input_source.on_incoming_event(event)
-                          })
+        else if self.world.query::<&mut Controller>()
+                          .filter(|item| item.lock().enabled)
+                          .map(|item| item.lock().input_source.on_incoming_event(event))
                           .any(|result| result) { return true }
         else { false }
     }
 
     pub fn update(&mut self) {
+        let profiler = self.world.profiler();
+        profiler.scope("State::update", "frame", || self.update_frame());
+    }
+
+    fn update_frame(&mut self) {
         let now = Instant::now();
         self.delta = now - self.prev_instant;
         self.prev_instant = now;
 
+        // The scheduler logs why if it can't run the systems, and tries again next frame:
+        let _ = self.world.update(self.delta);
+
         let build_proj_matrix = self.camera.view_proj_matrix();
 
-        //self.update_graphics_data();
+        // Entities were spawned, despawned or changed, so keep the drawn views in line with the world:
+        match self.world_events.drain() {
+            Some(events) => events.iter().for_each(|event| self.apply_world_event(event)),
+            // Fell behind the world, so start over from what is there now:
+            None => self.update_graphics_data()
+        }
+
         self.graphics_backend.update(build_proj_matrix);
         self.camera.update(self.delta);
 
     }
 
     pub fn render(&mut self){
+        let profiler = self.world.profiler();
+        profiler.scope("State::render", "frame", || self.render_frame());
+    }
+
+    fn render_frame(&mut self) {
         let fps =  1000 / (self.delta.as_millis() + 1u128);
 
-        self.graphics_backend.render(&self.graphics_cache, &self.loaded_models, fps)
+        let system_status: Vec<String> = self.world
+            .system_status()
+            .iter()
+            .map(|status| status.to_string())
+            .collect();
+
+        // Move the views in between the last two simulation snapshots, according to render time:
+        let now = Instant::now();
+        if let Some(snapshots) = self.world.resource::<TransformSnapshots>() {
+            let snapshots = snapshots.lock_resource_for_read();
+            let alpha = snapshots.alpha(now);
+
+            for model_views in self.graphics_cache.values() {
+                for (id, model_view) in model_views.iter() {
+                    if let Some(transform) = snapshots.interpolate(*id, alpha) {
+                        model_view.translate(&transform);
+                    }
+                }
+            }
+        }
+
+        self.graphics_backend.render(&self.graphics_cache, &self.loaded_models, fps, &system_status)
+    }
+
+    pub fn export_trace(&self) {
+        let path = "trace.json";
+
+        match self.world.profiler().write_chrome_trace(path) {
+            Ok(()) => info!("Wrote trace to {}", path),
+            Err(e) => error!("Couldn't write trace: {}", e)
+        }
+
+        for (name, stats) in self.world.profiler().stats() {
+            info!("{}: min {:?}, avg {:?}, p95 {:?}, p99 {:?}, max {:?}", name, stats.min, stats.avg, stats.p95, stats.p99, stats.max);
+        }
+    }
+
+    pub fn shutdown(&self) {
+        if let Err(e) = self.world.shutdown(Duration::from_secs(1)) {
+            error!("{}", e);
+        }
+    }
+
+    pub fn toggle_pause(&self) {
+        if let Some(clock) = self.world.resource::<SimulationClock>() {
+            let mut clock = clock.lock_resource_for_write();
+            if clock.is_paused() { clock.resume() } else { clock.pause() }
+        }
+    }
+
+    pub fn step_simulation(&self, n_ticks: u32) {
+        if let Some(clock) = self.world.resource::<SimulationClock>() {
+            let mut clock = clock.lock_resource_for_write();
+            clock.step(n_ticks);
+        }
+    }
+
+    pub fn scale_time(&self, factor: f32) {
+        if let Some(clock) = self.world.resource::<SimulationClock>() {
+            let mut clock = clock.lock_resource_for_write();
+            let time_scale = clock.time_scale() * factor;
+            clock.set_time_scale(time_scale);
+            info!("Simulation time scale: {}", clock.time_scale());
+        }
     }
 
     pub fn set_escape_status(&mut self, window: &Window, escape_status: bool) {
@@ -0,0 +1,270 @@
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::thread::{self, ThreadId};
+use std::time::{Duration, Instant};
+
+pub const DEFAULT_WINDOW: usize = 240;
+
+// Older trace events are dropped once this many have been recorded:
+const MAX_TRACE_EVENTS: usize = 65536;
+
+#[derive(Copy, Clone, Debug)]
+pub struct TimingStats {
+    pub samples: usize,
+    pub min: Duration,
+    pub avg: Duration,
+    pub max: Duration,
+    pub p50: Duration,
+    pub p95: Duration,
+    pub p99: Duration
+}
+
+// The most recent samples of a single timing:
+pub struct TimingWindow {
+    samples: VecDeque<Duration>,
+    capacity: usize
+}
+
+impl TimingWindow {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            samples: VecDeque::with_capacity(capacity),
+            capacity: capacity.max(1)
+        }
+    }
+
+    pub fn record(&mut self, sample: Duration) {
+        if self.samples.len() == self.capacity {
+            self.samples.pop_front();
+        }
+
+        self.samples.push_back(sample);
+    }
+
+    pub fn stats(&self) -> Option<TimingStats> {
+        if self.samples.is_empty() {
+            return None;
+        }
+
+        let mut sorted: Vec<Duration> = self.samples.iter().cloned().collect();
+        sorted.sort();
+
+        // Nearest-rank percentile:
+        let percentile = |p: f64| sorted[((p * sorted.len() as f64).ceil() as usize).max(1) - 1];
+        let total: Duration = sorted.iter().sum();
+
+        Some(TimingStats {
+            samples: sorted.len(),
+            min: sorted[0],
+            avg: total / sorted.len() as u32,
+            max: sorted[sorted.len() - 1],
+            p50: percentile(0.50),
+            p95: percentile(0.95),
+            p99: percentile(0.99)
+        })
+    }
+}
+
+struct TraceEvent {
+    name: String,
+    category: &'static str,
+    start: Duration,
+    duration: Duration,
+    thread: u32
+}
+
+struct ProfilerData {
+    epoch: Instant,
+    window: usize,
+    timings: BTreeMap<String, TimingWindow>,
+    trace: VecDeque<TraceEvent>,
+    // Chrome traces want small numeric thread ids:
+    threads: HashMap<ThreadId, u32>
+}
+
+// Collects named timings into rolling windows and keeps a trace of recent events that can be
+// exported for chrome://tracing. Clones share the same data.
+#[derive(Clone)]
+pub struct Profiler {
+    data: Arc<Mutex<ProfilerData>>
+}
+
+impl Profiler {
+    pub fn new(window: usize) -> Self {
+        Self {
+            data: Arc::new(Mutex::new(ProfilerData {
+                epoch: Instant::now(),
+                window,
+                timings: BTreeMap::new(),
+                trace: VecDeque::new(),
+                threads: HashMap::new()
+            }))
+        }
+    }
+
+    // Times the closure and records it under the given name:
+    pub fn scope<F: FnOnce() -> R, R>(&self, name: &str, category: &'static str, f: F) -> R {
+        let start = Instant::now();
+        let result = f();
+        self.record(name, category, start, start.elapsed());
+        result
+    }
+
+    pub fn record(&self, name: &str, category: &'static str, start: Instant, duration: Duration) {
+        let mut data = self.data.lock().expect("Profiler poisoned!");
+
+        let window = data.window;
+        data.timings
+            .entry(name.to_string())
+            .or_insert_with(|| TimingWindow::new(window))
+            .record(duration);
+
+        let next_thread = data.threads.len() as u32;
+        let thread = *data.threads.entry(thread::current().id()).or_insert(next_thread);
+
+        if data.trace.len() == MAX_TRACE_EVENTS {
+            data.trace.pop_front();
+        }
+
+        let start = start.saturating_duration_since(data.epoch);
+        data.trace.push_back(TraceEvent { name: name.to_string(), category, start, duration, thread });
+    }
+
+    pub fn stats_of(&self, name: &str) -> Option<TimingStats> {
+        self.data
+            .lock()
+            .expect("Profiler poisoned!")
+            .timings
+            .get(name)
+            .and_then(TimingWindow::stats)
+    }
+
+    // Sorted by name:
+    pub fn stats(&self) -> Vec<(String, TimingStats)> {
+        self.data
+            .lock()
+            .expect("Profiler poisoned!")
+            .timings
+            .iter()
+            .filter_map(|(name, window)| window.stats().map(|stats| (name.clone(), stats)))
+            .collect()
+    }
+
+    // Writes the recorded events in the Chrome trace event format:
+    pub fn write_chrome_trace<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let data = self.data.lock().expect("Profiler poisoned!");
+        let mut out = BufWriter::new(File::create(path)?);
+
+        write!(out, "{{\"traceEvents\":[")?;
+
+        for (i, event) in data.trace.iter().enumerate() {
+            if i > 0 {
+                write!(out, ",")?;
+            }
+
+            write!(
+                out,
+                "\n{{\"name\":\"{}\",\"cat\":\"{}\",\"ph\":\"X\",\"ts\":{},\"dur\":{},\"pid\":1,\"tid\":{}}}",
+                escape_json(&event.name),
+                escape_json(event.category),
+                event.start.as_micros(),
+                event.duration.as_micros(),
+                event.thread
+            )?;
+        }
+
+        write!(out, "\n],\"displayTimeUnit\":\"ms\"}}\n")?;
+        out.flush()
+    }
+}
+
+fn escape_json(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+
+    for c in text.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            c if (c as u32) < 0x20 => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+            c => escaped.push(c)
+        }
+    }
+
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    fn ms(n: u64) -> Duration {
+        Duration::from_millis(n)
+    }
+
+    fn window(samples: impl Iterator<Item = u64>) -> TimingWindow {
+        let mut window = TimingWindow::new(DEFAULT_WINDOW);
+        for sample in samples {
+            window.record(ms(sample));
+        }
+        window
+    }
+
+    #[test]
+    fn percentiles_use_the_nearest_rank() {
+        // Recorded out of order, so that the window has to be sorted:
+        let stats = window((1..=100).rev()).stats().unwrap();
+        assert_eq!((stats.min, stats.p50, stats.p95, stats.p99, stats.max), (ms(1), ms(50), ms(95), ms(99), ms(100)));
+        assert_eq!(stats.avg, Duration::from_micros(50500));
+
+        // The smallest sample that at least the given share of samples is less than or equal to:
+        let stats = window(1..=10).stats().unwrap();
+        assert_eq!((stats.p50, stats.p95, stats.p99), (ms(5), ms(10), ms(10)));
+
+        let stats = window(7..=7).stats().unwrap();
+        assert_eq!((stats.min, stats.p50, stats.p99, stats.max), (ms(7), ms(7), ms(7), ms(7)));
+
+        assert!(window(0..0).stats().is_none());
+    }
+
+    #[test]
+    fn windows_keep_the_latest_samples() {
+        let mut window = TimingWindow::new(3);
+        for sample in 1..=5 {
+            window.record(ms(sample));
+        }
+
+        let stats = window.stats().unwrap();
+        assert_eq!((stats.samples, stats.min, stats.max), (3, ms(3), ms(5)));
+    }
+
+    #[test]
+    fn json_is_escaped() {
+        assert_eq!(escape_json("plain"), "plain");
+        assert_eq!(escape_json("say \"hi\""), "say \\\"hi\\\"");
+        assert_eq!(escape_json("C:\\tmp"), "C:\\\\tmp");
+        assert_eq!(escape_json("a\nb\tc\u{1}"), "a\\u000ab\\u0009c\\u0001");
+        assert_eq!(escape_json("über"), "über");
+    }
+
+    #[test]
+    fn traces_hold_escaped_events() {
+        let profiler = Profiler::new(DEFAULT_WINDOW);
+        profiler.record("\"quoted\"\\system", "system", Instant::now(), ms(2));
+        profiler.record("frame", "render", Instant::now(), Duration::from_micros(1500));
+
+        let path = std::env::temp_dir().join(format!("profiler-test-{}.json", std::process::id()));
+        profiler.write_chrome_trace(&path).expect("Couldn't write trace!");
+        let trace = fs::read_to_string(&path).expect("Couldn't read trace!");
+        fs::remove_file(&path).expect("Couldn't remove trace!");
+
+        assert!(trace.starts_with("{\"traceEvents\":["));
+        assert!(trace.contains("\"name\":\"\\\"quoted\\\"\\\\system\",\"cat\":\"system\",\"ph\":\"X\""));
+        assert!(trace.contains("\"name\":\"frame\",\"cat\":\"render\",\"ph\":\"X\""));
+        assert!(trace.contains("\"dur\":2000,") && trace.contains("\"dur\":1500,"));
+        assert!(trace.trim_end().ends_with("],\"displayTimeUnit\":\"ms\"}"));
+    }
+}
@@ -1,6 +1,9 @@
 use crate::world::entity::component::Component;
 use super::prefab::Prefab;
 use crate::world::entity::Entity;
+use crate::world::entity::event::WorldEvent;
+use crate::world::entity::EntityContainer;
+use crate::world::entity::tag::Tag;
 use std::collections::HashMap;
 use std::any::TypeId;
 use std::sync::{Arc, Mutex, RwLock};
@@ -20,14 +23,19 @@ impl EntityBuilder{
     }
 
     pub fn with_name(self, name: &'static str) -> Self {
-        if let Ok(mut current_name) = self.entity.name.lock() {
+        if let Ok(mut current_name) = self.entity.inner.name.lock() {
             *current_name = String::from(name);
         }
         self
     }
 
+    pub fn with_tag<T: Into<Tag>>(self, tag: T) -> Self {
+        self.entity.add_tag(tag);
+        self
+    }
+
     pub fn with_child<T: Into<EntityBuilder>>(self, child: T) -> Self {
-        if let Ok(mut children) = self.entity.children.lock() {
+        if let Ok(mut children) = self.entity.inner.children.lock() {
             children.push(child.into().build());
         }
         
@@ -40,7 +48,7 @@ impl EntityBuilder{
             .map(|child| child.into().build())
             .collect::<Vec<Entity>>(); 
         
-        if let Ok(mut children) = self.entity.children.lock() {
+        if let Ok(mut children) = self.entity.inner.children.lock() {
             children.append(&mut built_children);
         }
 
@@ -52,8 +60,58 @@ impl EntityBuilder{
         self
     }
 
-    pub fn build(mut self) -> Entity {
-        self.entity.components = Arc::new(self.components);
+    // The entity doesn't belong to a world until it, or an ancestor, is spawned into one:
+    pub fn build(self) -> Entity {
+        // Rebuilding a live entity changes it in place, so others have to be told what changed:
+        let mut events = Vec::new();
+        let rebuilt = self.entity.is_alive();
+
+        if let Ok(mut components) = self.entity.inner.components.write() {
+            if rebuilt {
+                let id = self.entity.id();
+
+                for (type_id, component) in self.components.iter() {
+                    if !components.get(type_id).map_or(false, |previous| Arc::ptr_eq(previous, component)) {
+                        events.push(WorldEvent::ComponentAdded(id, *type_id));
+                    }
+                }
+
+                for type_id in components.keys().filter(|type_id| !self.components.contains_key(type_id)) {
+                    events.push(WorldEvent::ComponentRemoved(id, *type_id));
+                }
+            }
+
+            *components = self.components;
+        }
+
+        let mut spawned = Vec::new();
+
+        if let Ok(children) = self.entity.inner.children.lock() {
+            for child in children.iter() {
+                if child.parent().as_ref() != Some(&self.entity) {
+                    if let Ok(mut parent) = child.inner.parent.lock() {
+                        *parent = Some(self.entity.downgrade());
+                    }
+
+                    if rebuilt {
+                        spawned.push(child.clone());
+                    }
+                }
+            }
+        }
+
+        for child in spawned {
+            if let Some(registry) = self.entity.registry() {
+                registry.adopt(&child);
+            }
+
+            events.extend(child.query_all_entities(true).map(|entity| WorldEvent::EntitySpawned(entity.id())));
+        }
+
+        for e in events {
+            self.entity.emit(e);
+        }
+
         self.entity
     }
 }
@@ -62,7 +120,9 @@ impl From<Entity> for EntityBuilder {
     fn from(entity: Entity) -> Self {
         EntityBuilder{
             entity: entity.clone(),
-            components: entity.components
+            components: entity.inner.components
+                .read()
+                .expect("Couldn't read components!")
                 .iter()
                 .map(|(a,b)| (a.clone(), b.clone()))
                 .collect::<HashMap<_,_>>()
@@ -4,11 +4,13 @@ use winit::event::{WindowEvent, ElementState, VirtualKeyCode, KeyboardInput};
 use cgmath::{Point3, Vector3, EuclideanSpace, InnerSpace};
 use crate::world::entity::component::{Component, ComponentManager};
 use crate::world::entity::component::transform::Transform;
+use crate::world::entity::registry::EntityId;
 
 pub use crate::backend::graphics::camera::CameraPerspective;
 pub use crate::backend::input::camera::CameraController;
 
 pub struct Camera {
+    pub enabled: bool,
     pub perspective: CameraPerspective,
     pub controller: Box<dyn CameraController>
 }
@@ -16,15 +18,23 @@ pub struct Camera {
 impl Camera {
     pub fn new<T: 'static + CameraController>(perspective: CameraPerspective, controller: T) -> Camera {
         Camera { 
+            enabled: true,
             perspective, 
             controller: Box::new(controller)
         } 
     }
 }
 
+// World resource pointing out the entity the renderer looks through:
+pub struct ActiveCamera(pub EntityId);
+
 impl Component for Camera {
     fn enabled(&self) -> bool {
-        unimplemented!()
+        self.enabled
+    }
+
+    fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
     }
 }
 
@@ -0,0 +1,176 @@
+use std::sync::Arc;
+use std::path::Path;
+use std::f32::consts::PI;
+use crate::world::entity::component::{Component, ComponentClone};
+use cgmath::{Vector3, InnerSpace, ElementWise};
+
+// Hulls with more points than this are cut down to their extreme points along as many directions:
+pub const MAX_HULL_POINTS: usize = 256;
+
+#[derive(Clone, Debug)]
+pub enum Shape {
+    Sphere { radius: f32 },
+    // Stays aligned with the world axes, whatever the rotation of the entity:
+    Aabb { half_extents: Vector3<f32> },
+    // Turns with the entity:
+    Obb { half_extents: Vector3<f32> },
+    // A segment along the local y axis, inflated by the radius:
+    Capsule { half_height: f32, radius: f32 },
+    // Shared, since every entity showing the same mesh gets the same hull:
+    ConvexHull { points: Arc<Vec<Vector3<f32>>> }
+}
+
+impl Shape {
+    // The point of the shape furthest along the direction, in local space:
+    pub fn support(&self, direction: Vector3<f32>) -> Vector3<f32> {
+        match *self {
+            Shape::Sphere { radius } => normalize_or_zero(direction) * radius,
+            Shape::Aabb { half_extents } | Shape::Obb { half_extents } => Vector3::new(
+                half_extents.x.copysign(direction.x),
+                half_extents.y.copysign(direction.y),
+                half_extents.z.copysign(direction.z)
+            ),
+            Shape::Capsule { half_height, radius } =>
+                Vector3::new(0.0, half_height.copysign(direction.y), 0.0) + normalize_or_zero(direction) * radius,
+            Shape::ConvexHull { ref points } => furthest_along(points, direction)
+                .map(|index| points[index])
+                .unwrap_or_else(|| Vector3::new(0.0, 0.0, 0.0))
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct Collider {
+    pub enabled: bool,
+    pub shape: Shape,
+    // Where the shape sits relative to the entity, in local space:
+    pub offset: Vector3<f32>
+}
+
+impl Collider {
+    pub fn new(shape: Shape) -> Collider {
+        Collider {
+            enabled: true,
+            shape,
+            offset: Vector3 {x: 0.0, y: 0.0, z: 0.0}
+        }
+    }
+
+    pub fn sphere(radius: f32) -> Collider {
+        Collider::new(Shape::Sphere { radius })
+    }
+
+    pub fn aabb(half_extents: Vector3<f32>) -> Collider {
+        Collider::new(Shape::Aabb { half_extents })
+    }
+
+    pub fn obb(half_extents: Vector3<f32>) -> Collider {
+        Collider::new(Shape::Obb { half_extents })
+    }
+
+    pub fn capsule(half_height: f32, radius: f32) -> Collider {
+        Collider::new(Shape::Capsule { half_height, radius })
+    }
+
+    // Only the outermost points matter to the narrowphase, so the points don't have to form a
+    // hull already. Large point clouds are approximated by their extreme points.
+    pub fn convex_hull(points: Vec<Vector3<f32>>) -> Collider {
+        let points = if points.len() > MAX_HULL_POINTS { extreme_points(&points) } else { points };
+        Collider::new(Shape::ConvexHull { points: Arc::new(points) })
+    }
+
+    // Loads the mesh the same way the renderer does, and wraps all of its vertices:
+    pub fn convex_hull_from_obj<P: AsRef<Path>>(path: P) -> Result<Collider, tobj::LoadError> {
+        let (obj_models, _) = tobj::load_obj(path.as_ref(), true)?;
+
+        let points = obj_models
+            .iter()
+            .flat_map(|model| model.mesh.positions.chunks(3))
+            .map(|position| Vector3::new(position[0], position[1], position[2]))
+            .collect();
+
+        Ok(Collider::convex_hull(points))
+    }
+
+    pub fn with_offset(mut self, offset: Vector3<f32>) -> Self {
+        self.offset = offset;
+        self
+    }
+
+    // The moment of inertia per unit of mass about the origin of the entity, averaged over the
+    // three axes, for a solid body of this shape. Shapes other than spheres and boxes count as
+    // their bounding box:
+    pub fn inertia_per_mass(&self, scale: Vector3<f32>) -> f32 {
+        let scale = Vector3::new(scale.x.abs(), scale.y.abs(), scale.z.abs());
+
+        let about_center = match self.shape {
+            // An ellipsoid, if the scale isn't the same along every axis:
+            Shape::Sphere { radius } => 2.0 / 15.0 * (scale * radius).magnitude2(),
+            _ => {
+                let half_extents = Vector3::new(
+                    self.shape.support(Vector3::unit_x()).x - self.shape.support(-Vector3::unit_x()).x,
+                    self.shape.support(Vector3::unit_y()).y - self.shape.support(-Vector3::unit_y()).y,
+                    self.shape.support(Vector3::unit_z()).z - self.shape.support(-Vector3::unit_z()).z
+                ) * 0.5;
+
+                2.0 / 9.0 * half_extents.mul_element_wise(scale).magnitude2()
+            }
+        };
+
+        // Moved away from the origin by the offset, as by the parallel axis theorem:
+        about_center + 2.0 / 3.0 * self.offset.mul_element_wise(scale).magnitude2()
+    }
+}
+
+impl Component for Collider {
+    fn enabled(&self) -> bool {
+        self.enabled
+    }
+
+    fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+    }
+
+    fn duplicate(&self) -> Option<Box<dyn Component>> {
+        Some(self.clone_boxed())
+    }
+}
+
+fn normalize_or_zero(v: Vector3<f32>) -> Vector3<f32> {
+    if v.magnitude2() > 0.0 { v.normalize() } else { v }
+}
+
+// The first of the points that reach the furthest along the direction:
+fn furthest_along(points: &[Vector3<f32>], direction: Vector3<f32>) -> Option<usize> {
+    let mut furthest: Option<(usize, f32)> = None;
+
+    for (index, point) in points.iter().enumerate() {
+        let distance = point.dot(direction);
+        if furthest.map_or(true, |(_, max_distance)| distance > max_distance) {
+            furthest = Some((index, distance));
+        }
+    }
+
+    furthest.map(|(index, _)| index)
+}
+
+// The points furthest along directions spread evenly over a sphere (a Fibonacci lattice), in the
+// order they were given:
+fn extreme_points(points: &[Vector3<f32>]) -> Vec<Vector3<f32>> {
+    let golden_angle = PI * (3.0 - 5f32.sqrt());
+
+    let mut indices: Vec<usize> = (0..MAX_HULL_POINTS)
+        .filter_map(|i| {
+            let y = 1.0 - 2.0 * (i as f32 + 0.5) / MAX_HULL_POINTS as f32;
+            let r = (1.0 - y * y).sqrt();
+            let theta = golden_angle * i as f32;
+            let direction = Vector3::new(r * theta.cos(), y, r * theta.sin());
+
+            furthest_along(points, direction)
+        })
+        .collect();
+
+    indices.sort();
+    indices.dedup();
+    indices.into_iter().map(|index| points[index]).collect()
+}
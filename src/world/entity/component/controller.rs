@@ -5,13 +5,14 @@ use crate::world::entity::component::Component;
 use crate::backend::input::entity::EntityController;
 
 pub struct Controller {
+    pub enabled: bool,
     pub input_source: Box<dyn EntityController>,
-    
 }
 
 impl Controller {
     pub fn new<T: 'static + EntityController>(controller: T) -> Self {
         Self {
+            enabled: true,
             input_source: Box::new(controller)
         }
     }
@@ -23,6 +24,10 @@ impl Controller {
 
 impl Component for Controller {
     fn enabled(&self) -> bool {
-        unimplemented!()
+        self.enabled
+    }
+
+    fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
     }
 }
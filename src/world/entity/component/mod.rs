@@ -1,50 +1,137 @@
 use std::any::Any;
-use std::sync::{MutexGuard, Mutex, Arc, RwLockWriteGuard, RwLock, RwLockReadGuard};
-use crate::world::entity::{Entity, EntityContainer, EntityIterator};
+use std::sync::{MutexGuard, Mutex, Arc, RwLockWriteGuard, RwLock, RwLockReadGuard, Weak};
+use crate::world::entity::{Entity, EntityInner, EntityContainer, EntityIterator};
 use std::marker::PhantomData;
 use std::ops::{DerefMut, Deref};
 use downcast_rs::Downcast;
+use std::any::{TypeId, type_name};
+use crate::world::entity::tag::Tag;
+use crate::util::glob_match;
+use crate::world::entity::registry::EntityId;
+use crate::world::entity::event::WorldEvent;
 
 pub mod camera;
 pub mod transform;
 pub mod model;
 pub mod rigid_body;
 pub mod controller;
+pub mod collider;
+
+pub enum ComponentMask {
+    Tag(Tag),
+    EntityName(String),
+    Type(TypeId),
+    All(Vec<ComponentMask>),
+    Any(Vec<ComponentMask>),
+    Not(Box<ComponentMask>)
+}
+
+impl ComponentMask {
+    pub fn tag<T: Into<Tag>>(tag: T) -> ComponentMask {
+        ComponentMask::Tag(tag.into())
+    }
+
+    // Supports `*` and `?` wildcards, e.g. "cube*":
+    pub fn name(pattern: &str) -> ComponentMask {
+        ComponentMask::EntityName(pattern.to_string())
+    }
+
+    pub fn component<C: Component>() -> ComponentMask {
+        ComponentMask::Type(TypeId::of::<C>())
+    }
+
+    pub fn and(self, other: ComponentMask) -> ComponentMask {
+        match self {
+            ComponentMask::All(mut masks) => { masks.push(other); ComponentMask::All(masks) },
+            mask => ComponentMask::All(vec![mask, other])
+        }
+    }
 
-enum ComponentMask {
-    Tag,
-    EntityName,
-    Type
+    pub fn or(self, other: ComponentMask) -> ComponentMask {
+        match self {
+            ComponentMask::Any(mut masks) => { masks.push(other); ComponentMask::Any(masks) },
+            mask => ComponentMask::Any(vec![mask, other])
+        }
+    }
+
+    pub fn inverted(self) -> ComponentMask {
+        match self {
+            ComponentMask::Not(mask) => *mask,
+            mask => ComponentMask::Not(Box::new(mask))
+        }
+    }
+
+    pub fn matches(&self, entity: &Entity) -> bool {
+        match self {
+            ComponentMask::Tag(tag) => entity.has_tag(tag.clone()),
+            ComponentMask::EntityName(pattern) => glob_match(pattern, entity.inner.name.lock().unwrap().deref()),
+            ComponentMask::Type(type_id) => entity.has_component_of_type(*type_id),
+            ComponentMask::All(masks) => masks.iter().all(|mask| mask.matches(entity)),
+            ComponentMask::Any(masks) => masks.iter().any(|mask| mask.matches(entity)),
+            ComponentMask::Not(mask) => !mask.matches(entity)
+        }
+    }
 }
 
 pub trait Component: Downcast + Any + Send + Sync {
     fn enabled(&self) -> bool;
-}
 
+    fn set_enabled(&mut self, enabled: bool);
 
+    // Opt-in: components that can be copied return a fresh instance, see ComponentClone.
+    fn duplicate(&self) -> Option<Box<dyn Component>> {
+        None
+    }
+
+    fn component_name(&self) -> &'static str {
+        type_name::<Self>()
+    }
+}
 
 impl_downcast!(Component);
 
-impl Clone for Box<dyn Component>{
-    fn clone(&self) -> Self {
-        self.clone()
+pub trait ComponentClone {
+    fn clone_boxed(&self) -> Box<dyn Component>;
+}
+
+impl<C: Component + Clone> ComponentClone for C {
+    fn clone_boxed(&self) -> Box<dyn Component> {
+        Box::new(self.clone())
     }
 }
 
-#[derive(Clone)]
 pub struct ComponentManager<C: Component>{
+    // Held weakly, so that caching a manager doesn't keep a despawned entity around:
+    owner: Weak<EntityInner>,
     inner: Arc<RwLock<Box<dyn Component>>>,
     phantom: PhantomData<C>
 }
 
+impl<C: Component> Clone for ComponentManager<C>{
+    fn clone(&self) -> Self {
+        ComponentManager {
+            owner: self.owner.clone(),
+            inner: self.inner.clone(),
+            phantom: PhantomData
+        }
+    }
+}
+
 impl<C: Component> ComponentManager<C>{
-    pub fn init(inner: Arc<RwLock<Box<dyn Component>>>) -> ComponentManager<C>{
+    pub fn init(owner: &Entity, inner: Arc<RwLock<Box<dyn Component>>>) -> ComponentManager<C>{
         ComponentManager {
+            owner: owner.downgrade(),
             inner,
             phantom: PhantomData
         }
     }
 
+    // The entity the component belongs to:
+    // Invalid once the entity has been dropped:
+    pub fn owner(&self) -> EntityId {
+        self.owner.upgrade().map_or(EntityId::INVALID, |owner| Entity::from_inner(owner).id())
+    }
+
     pub fn peek<F, R>(&self, f: F) -> Option<R>
         where F: Fn(&C) -> R{
 
@@ -70,6 +157,18 @@ impl<C: Component> ComponentManager<C>{
         }
     }
 
+    pub fn enabled(&self) -> bool {
+        self.peek(|component| component.enabled()).unwrap_or(false)
+    }
+
+    // Systems and the renderer drop disabled components from their member lists on the next tick:
+    pub fn set_enabled(&self, enabled: bool) {
+        self.peek_mut(|component| component.set_enabled(enabled));
+        if let Some(owner) = self.owner.upgrade().map(Entity::from_inner) {
+            owner.emit(WorldEvent::ComponentEnabled(owner.id(), TypeId::of::<C>(), enabled));
+        }
+    }
+
     pub fn lock_component_for_read<'a>(&'a self) -> ComponentReadAccess<'a, C>{
         ComponentReadAccess {
             guard: self.inner.read().unwrap(),
@@ -145,7 +244,7 @@ impl<T: Component> ComponentIterator<T> {
         Self {
             component: PhantomData,
             entity: entity.clone(),
-            entity_iter: EntityIterator::new(entity.clone(), None, include_parent, false)
+            entity_iter: entity.query_entities(include_parent)
         }
     }
 
@@ -200,4 +299,93 @@ impl<T, F> Iterator for FilteredComponentIterator<T, F> where T: Component, F: F
 
         return None;
     }
-}
\ No newline at end of file
+}
+
+pub struct MaskedEntityIterator {
+    iter: EntityIterator,
+    mask: ComponentMask
+}
+
+impl MaskedEntityIterator {
+    pub fn new(iter: EntityIterator, mask: ComponentMask) -> Self {
+        Self { iter, mask }
+    }
+}
+
+impl Iterator for MaskedEntityIterator {
+    type Item = Entity;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some(entity) = self.iter.next() {
+            if self.mask.matches(&entity) {
+                return Some(entity);
+            }
+        }
+
+        return None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::entity::builder::EntityBuilder;
+    use crate::world::entity::component::transform::Transform;
+    use crate::world::entity::component::rigid_body::RigidBody;
+
+    struct Enemy;
+
+    fn world() -> Entity {
+        EntityBuilder::new()
+            .with_name("root")
+            .with_child(EntityBuilder::new().with_name("cube1").with_tag("obstacle").with_component(Transform::new()))
+            .with_child(EntityBuilder::new().with_name("cube2").with_tag(Tag::of::<Enemy>()).with_component(Transform::new()).with_component(RigidBody::new(1.0)))
+            .with_child(EntityBuilder::new().with_name("sphere").with_tag("obstacle").with_tag(Tag::of::<Enemy>()))
+            .build()
+    }
+
+    fn names(entities: MaskedEntityIterator) -> Vec<String> {
+        entities.map(|entity| entity.name()).collect()
+    }
+
+    #[test]
+    fn labels_and_typed_tags_are_distinct() {
+        let world = world();
+
+        assert_eq!(names(world.query_entities_by_tag("obstacle", false)), vec!["cube1", "sphere"]);
+        assert_eq!(names(world.query_entities_by_tag(Tag::of::<Enemy>(), false)), vec!["cube2", "sphere"]);
+        assert!(names(world.query_entities_by_tag("Enemy", false)).is_empty());
+
+        let sphere = world.query_entity_by_name("sphere", false).next().unwrap();
+        assert!(sphere.remove_tag("obstacle"));
+        assert!(!sphere.remove_tag("obstacle"));
+        assert_eq!(names(world.query_entities_by_tag("obstacle", false)), vec!["cube1"]);
+    }
+
+    #[test]
+    fn names_are_globbed() {
+        let world = world();
+
+        assert_eq!(names(world.query_entities_by_mask(ComponentMask::name("cube*"), false)), vec!["cube1", "cube2"]);
+        assert_eq!(names(world.query_entities_by_mask(ComponentMask::name("cube?"), true)), vec!["cube1", "cube2"]);
+        assert_eq!(names(world.query_entities_by_mask(ComponentMask::name("*o*"), true)), vec!["root"]);
+        assert!(names(world.query_entities_by_mask(ComponentMask::name("cube"), false)).is_empty());
+    }
+
+    #[test]
+    fn masks_combine() {
+        let world = world();
+
+        let enemy_bodies = ComponentMask::tag(Tag::of::<Enemy>()).and(ComponentMask::component::<RigidBody>());
+        assert_eq!(names(world.query_entities_by_mask(enemy_bodies, false)), vec!["cube2"]);
+
+        let either = ComponentMask::component::<RigidBody>().or(ComponentMask::name("sph*"));
+        assert_eq!(names(world.query_entities_by_mask(either, false)), vec!["cube2", "sphere"]);
+
+        let untransformed = ComponentMask::component::<Transform>().inverted();
+        assert_eq!(names(world.query_entities_by_mask(untransformed, true)), vec!["root", "sphere"]);
+
+        let twice = ComponentMask::tag("obstacle").inverted().inverted();
+        assert_eq!(names(world.query_entities_by_mask(twice, false)), vec!["cube1", "sphere"]);
+    }
+}
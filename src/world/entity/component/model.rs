@@ -1,30 +1,36 @@
-use std::sync::Arc;
-use crate::world::entity::component::{Component};
-use crate::backend::graphics::model_view::ModelView;
-use crate::backend::BackendProxy;
+use crate::world::entity::component::{Component, ComponentClone};
+use crate::world::entity::component::transform::Transform;
 
+// Only describes what to draw and where; the renderer creates and owns the GPU resources, so a
+// world with graphics models can be simulated without a graphics backend.
+#[derive(Clone)]
 pub struct GraphicsModel {
+    pub enabled: bool,
     pub path_to_obj: &'static str,
-    pub view: Arc<ModelView> 
+    // Absolute transform, kept up to date by the TranslateSystem:
+    pub transform: Transform
 }
 
 impl GraphicsModel {
-    pub fn new(path_to_obj: &'static str, backend_proxy: &BackendProxy) -> GraphicsModel {
+    pub fn new(path_to_obj: &'static str) -> GraphicsModel {
         GraphicsModel {
+            enabled: true,
             path_to_obj,
-            view: Arc::new(backend_proxy.instantiate_model_view())
+            transform: Transform::new()
         }
     }
 }
 
 impl Component for GraphicsModel {
     fn enabled(&self) -> bool {
-        true
+        self.enabled
+    }
+
+    fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
     }
-}
 
-impl Clone for GraphicsModel {
-    fn clone(&self) -> Self {
-        unimplemented!()
+    fn duplicate(&self) -> Option<Box<dyn Component>> {
+        Some(self.clone_boxed())
     }
-}
\ No newline at end of file
+}
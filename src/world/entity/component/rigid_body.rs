@@ -1,9 +1,11 @@
-use crate::world::entity::component::Component;
+use crate::world::entity::component::{Component, ComponentClone};
+use crate::world::entity::component::collider::Collider;
 use cgmath::Vector3;
-use std::collections::HashMap;
-
+use std::collections::BTreeMap;
 
+#[derive(Clone)]
 pub struct RigidBody {
+    pub enabled: bool,
     pub mass: f32,
     pub movable: bool,
     pub last_absolute_position: Vector3<f32>,
@@ -11,14 +13,24 @@ pub struct RigidBody {
     pub acceleration: Vector3<f32>,
     pub angular_velocity: Vector3<f32>,
     pub angular_acceleration: Vector3<f32>,
+    // About any axis through the center, per unit of mass so that it follows the mass. The same
+    // for every axis, as if the body were a sphere. None takes it from the collider of the body:
+    pub inertia_per_mass: Option<f32>,
+    // How much of the approaching speed is kept after a collision, from 0 (none) to 1 (all):
+    pub restitution: f32,
+    // Coulomb friction coefficient:
+    pub friction: f32,
+    // Scales the gravity the body feels, but not how much it pulls on others; 0 makes it float:
+    pub gravity_scale: f32,
 
-    // Internal:
-    forces: HashMap<&'static str, Vector3<f32>>
+    // Internal (ordered, so that the net force is summed up the same way every time):
+    forces: BTreeMap<&'static str, Vector3<f32>>
 }
 
 impl RigidBody{
     pub fn new(mass: f32) -> RigidBody {
         RigidBody { 
+            enabled: true,
             mass, 
             movable: true,
             last_absolute_position: Vector3 {x: 0.0, y: 0.0, z: 0.0},
@@ -26,11 +38,55 @@ impl RigidBody{
             acceleration: Vector3 {x: 0.0, y: 0.0, z: 0.0},
             angular_velocity: Vector3 {x: 0.0, y: 0.0, z: 0.0},
             angular_acceleration: Vector3 {x: 0.0, y: 0.0, z: 0.0},
+            inertia_per_mass: None,
+            restitution: 0.3,
+            friction: 0.5,
+            gravity_scale: 1.0,
 
-            forces: HashMap::new(),
+            forces: BTreeMap::new(),
          }
     }
 
+    pub fn with_restitution(mut self, restitution: f32) -> Self {
+        self.restitution = restitution;
+        self
+    }
+
+    pub fn with_friction(mut self, friction: f32) -> Self {
+        self.friction = friction;
+        self
+    }
+
+    pub fn with_gravity_scale(mut self, gravity_scale: f32) -> Self {
+        self.gravity_scale = gravity_scale;
+        self
+    }
+
+    pub fn with_inertia_per_mass(mut self, inertia_per_mass: f32) -> Self {
+        self.inertia_per_mass = Some(inertia_per_mass);
+        self
+    }
+
+    // Given the collider of the body and the scale it is drawn at. Without either a collider or
+    // an inertia of its own, the body is taken to be a solid sphere of unit radius:
+    pub fn inertia(&self, collider: Option<&Collider>, scale: Vector3<f32>) -> f32 {
+        let inertia_per_mass = self.inertia_per_mass
+            .or_else(|| collider.map(|collider| collider.inertia_per_mass(scale)))
+            .unwrap_or(0.4);
+
+        inertia_per_mass * self.mass
+    }
+
+    // Bodies that can't be moved, or are disabled, behave as if their mass were infinite:
+    pub fn inverse_mass(&self) -> f32 {
+        if self.movable && self.enabled && self.mass > 0.0 { 1.0 / self.mass } else { 0.0 }
+    }
+
+    pub fn inverse_inertia(&self, collider: Option<&Collider>, scale: Vector3<f32>) -> f32 {
+        let inertia = self.inertia(collider, scale);
+        if self.movable && self.enabled && inertia > 0.0 { 1.0 / inertia } else { 0.0 }
+    }
+
     pub fn commit_force(&mut self, force_desc: &'static str, mut force: Vector3<f32>) {
         if f32::is_nan(force.x) { force.x = 0.0; }
         if f32::is_nan(force.y) { force.y = 0.0; }
@@ -39,6 +95,10 @@ impl RigidBody{
         self.forces.insert(force_desc, force);
     }
 
+    pub fn force(&self, force_desc: &'static str) -> Option<Vector3<f32>> {
+        self.forces.get(force_desc).cloned()
+    }
+
     pub fn net_force(&self) -> Vector3<f32> {
         self.forces.values().map(|force| force).sum()
     }
@@ -46,6 +106,14 @@ impl RigidBody{
 
 impl Component for RigidBody {
     fn enabled(&self) -> bool {
-        unimplemented!()
+        self.enabled
+    }
+
+    fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+    }
+
+    fn duplicate(&self) -> Option<Box<dyn Component>> {
+        Some(self.clone_boxed())
     }
 }
\ No newline at end of file
@@ -1,11 +1,10 @@
 use std::sync::Arc;
-use crate::world::entity::component::{Component};
-use crate::backend::graphics::model_view::{ModelView};
+use crate::world::entity::component::{Component, ComponentClone};
 use failure::_core::any::Any;
 use cgmath::{Vector3, Quaternion};
-use crate::backend::BackendProxy;
 
 pub struct Transform {
+    pub enabled: bool,
     pub position: Vector3<f32>,
     pub angular_rotation: Vector3<f32>,
     pub scale: Vector3<f32>,
@@ -14,6 +13,7 @@ pub struct Transform {
 impl Transform {
     pub fn new() -> Transform {
         Transform {
+            enabled: true,
             position: Vector3 {x: 0.0, y: 0.0, z: 0.0},
             angular_rotation: Vector3 {x: 0.0, y: 0.0, z: 0.0},
             scale: Vector3 {x: 1.0, y: 1.0, z: 1.0},
@@ -38,17 +38,50 @@ impl Transform {
 
         self
     }
+
+    // Blends linearly from this transform (t = 0) to the other one (t = 1):
+    pub fn lerp(&self, other: &Transform, t: f32) -> Transform {
+        Transform {
+            enabled: other.enabled,
+            position: self.position + (other.position - self.position) * t,
+            angular_rotation: self.angular_rotation + (other.angular_rotation - self.angular_rotation) * t,
+            scale: self.scale + (other.scale - self.scale) * t
+        }
+    }
+
+    // The inverse of with_offset, i.e. `parent.with_offset(&local) == self`:
+    pub fn relative_to(&self, parent: &Transform) -> Transform {
+        Transform {
+            enabled: self.enabled,
+            position: self.position - parent.position,
+            angular_rotation: self.angular_rotation - parent.angular_rotation,
+            scale: Vector3 {
+                x: self.scale.x / parent.scale.x,
+                y: self.scale.y / parent.scale.y,
+                z: self.scale.z / parent.scale.z
+            }
+        }
+    }
 }
 
 impl Component for Transform {
     fn enabled(&self) -> bool {
-        true
+        self.enabled
+    }
+
+    fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+    }
+
+    fn duplicate(&self) -> Option<Box<dyn Component>> {
+        Some(self.clone_boxed())
     }
 }
 
 impl Clone for Transform{
     fn clone(&self) -> Self {
         Transform {
+            enabled: self.enabled,
             position: self.position.clone(),
             angular_rotation: self.angular_rotation.clone(),
             scale: self.scale.clone(),
@@ -0,0 +1,169 @@
+use std::any::TypeId;
+use std::sync::{Arc, Mutex};
+use std::sync::mpsc::{sync_channel, Receiver, SyncSender, TryRecvError};
+use crate::world::entity::registry::EntityId;
+
+// Structural changes to the world. Systems and the renderer subscribe to these to keep their
+// cached member lists current instead of querying the whole world again.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum WorldEvent {
+    // The entity was attached beneath a parent, one event per entity in the attached subtree:
+    EntitySpawned(EntityId),
+    // The entity was despawned, one event per entity in the subtree. Its id is gone for good:
+    EntityDespawned(EntityId),
+    // The entity was detached from its parent, one event per entity in the subtree. It still
+    // belongs to the world and keeps its id, e.g. to be spawned beneath another parent:
+    EntityDetached(EntityId),
+    // Enabling or disabling an entity affects its whole subtree:
+    EntityEnabled(EntityId, bool),
+    ComponentAdded(EntityId, TypeId),
+    ComponentRemoved(EntityId, TypeId),
+    ComponentEnabled(EntityId, TypeId, bool)
+}
+
+impl WorldEvent {
+    pub fn entity(&self) -> EntityId {
+        match *self {
+            WorldEvent::EntitySpawned(id) |
+            WorldEvent::EntityDespawned(id) |
+            WorldEvent::EntityDetached(id) |
+            WorldEvent::EntityEnabled(id, _) |
+            WorldEvent::ComponentAdded(id, _) |
+            WorldEvent::ComponentRemoved(id, _) |
+            WorldEvent::ComponentEnabled(id, _, _) => id
+        }
+    }
+}
+
+// How many events a subscriber may fall behind by before it is dropped from the bus:
+pub const SUBSCRIPTION_CAPACITY: usize = 1 << 14;
+
+// Delivers the events of one world to everyone subscribed to it.
+#[derive(Clone, Default)]
+pub struct EventBus {
+    subscribers: Arc<Mutex<Vec<SyncSender<WorldEvent>>>>
+}
+
+impl EventBus {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // The subscription receives every event emitted from now on, in the order they were emitted.
+    // Dropping it unsubscribes.
+    pub fn subscribe(&self) -> Subscription {
+        Subscription {
+            bus: self.clone(),
+            receiver: self.add_subscriber()
+        }
+    }
+
+    fn add_subscriber(&self) -> Receiver<WorldEvent> {
+        let (sender, receiver) = sync_channel(SUBSCRIPTION_CAPACITY);
+        self.subscribers.lock().expect("Event subscribers poisoned!").push(sender);
+        receiver
+    }
+
+    // Never blocks: subscribers that have been dropped, or that are too far behind to take
+    // another event, are removed from the bus.
+    pub fn emit(&self, event: WorldEvent) {
+        self.subscribers
+            .lock()
+            .expect("Event subscribers poisoned!")
+            .retain(|subscriber| subscriber.try_send(event).is_ok());
+    }
+
+    pub fn subscriber_count(&self) -> usize {
+        self.subscribers.lock().expect("Event subscribers poisoned!").len()
+    }
+}
+
+pub struct Subscription {
+    bus: EventBus,
+    receiver: Receiver<WorldEvent>
+}
+
+impl Subscription {
+    // The events emitted since the last call. Returns None if events have been missed since,
+    // because the subscriber fell too far behind; the subscription is renewed then, and the
+    // subscriber has to catch up with the world itself, e.g. by querying it again.
+    pub fn drain(&mut self) -> Option<Vec<WorldEvent>> {
+        let mut events = Vec::new();
+
+        loop {
+            match self.receiver.try_recv() {
+                Ok(event) => events.push(event),
+                Err(TryRecvError::Empty) => return Some(events),
+                Err(TryRecvError::Disconnected) => {
+                    self.receiver = self.bus.add_subscriber();
+                    return None;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::entity::builder::EntityBuilder;
+    use crate::world::entity::registry::EntityRegistry;
+    use crate::world::entity::EntityContainer;
+
+    #[test]
+    fn dropped_and_slow_subscribers_are_pruned() {
+        let bus = EventBus::new();
+        let dropped = bus.subscribe();
+        let mut slow = bus.subscribe();
+        let mut current = bus.subscribe();
+        drop(dropped);
+
+        for _ in 0..SUBSCRIPTION_CAPACITY {
+            bus.emit(WorldEvent::EntitySpawned(EntityId::INVALID));
+        }
+        assert_eq!(bus.subscriber_count(), 2);
+        assert_eq!(current.drain().map(|events| events.len()), Some(SUBSCRIPTION_CAPACITY));
+
+        bus.emit(WorldEvent::EntitySpawned(EntityId::INVALID));
+        assert_eq!(bus.subscriber_count(), 1);
+        assert_eq!(current.drain().map(|events| events.len()), Some(1));
+
+        // A pruned subscriber learns that it missed events, and then hears of new ones again:
+        assert!(slow.drain().is_none());
+        bus.emit(WorldEvent::EntitySpawned(EntityId::INVALID));
+        assert_eq!(slow.drain().map(|events| events.len()), Some(1));
+    }
+
+    #[test]
+    fn worlds_dont_hear_each_other() {
+        let (first, second) = (EntityRegistry::new(), EntityRegistry::new());
+        let (a, b) = (EntityBuilder::new().build(), EntityBuilder::new().build());
+        first.adopt(&a);
+        second.adopt(&b);
+
+        let mut events = first.events().subscribe();
+        b.spawn_entity(EntityBuilder::new().build());
+        assert_eq!(events.drain(), Some(Vec::new()));
+
+        let child = EntityBuilder::new().build();
+        a.spawn_entity(child.clone());
+        assert_eq!(events.drain(), Some(vec![WorldEvent::EntitySpawned(child.id())]));
+    }
+
+    #[test]
+    fn detaching_isnt_despawning() {
+        let registry = EntityRegistry::new();
+        let root = EntityBuilder::new().with_child(EntityBuilder::new()).build();
+        registry.adopt(&root);
+
+        let mut events = registry.events().subscribe();
+        let child = root.query_direct_children().next().unwrap();
+        child.detach();
+
+        assert_eq!(events.drain(), Some(vec![WorldEvent::EntityDetached(child.id())]));
+        assert!(registry.lookup(child.id()).unwrap() == child);
+
+        child.despawn();
+        assert!(matches!(events.drain().as_deref(), Some([WorldEvent::EntityDespawned(_)])));
+    }
+}
@@ -4,24 +4,50 @@ use crate::world::entity::component::transform::Transform;
 pub mod component;
 pub mod prefab;
 pub mod builder;
+pub mod registry;
+pub mod query;
+pub mod tag;
+pub mod traversal;
+pub mod event;
 
 use std::any::{Any, TypeId};
-use std::sync::{RwLock, RwLockWriteGuard, Arc, Mutex};
-use std::collections::HashMap;
+use std::sync::{RwLock, RwLockWriteGuard, Arc, Mutex, Weak};
+use std::collections::{HashMap, HashSet};
 use std::slice::Iter;
 use std::error::Error;
 use std::fmt::Display;
 use failure::_core::fmt::Formatter;
 use std::marker::PhantomData;
-use std::ops::Deref;
 use std::fmt;
+use crate::world::entity::registry::{EntityId, EntityRegistry};
+use crate::world::entity::query::{Query, WorldQuery};
+use crate::world::entity::tag::Tag;
+use crate::world::entity::component::{ComponentMask, MaskedEntityIterator};
+use crate::util::glob_match;
+use crate::world::entity::traversal::{Traversal, TraversalIterator};
+use crate::world::entity::event::WorldEvent;
 
+// Clones share the same entity, which lives as long as any of them does.
 pub struct Entity {
-    pub enabled: Arc<Mutex<bool>>,
-    pub invalidated: Arc<Mutex<bool>>,
-    pub name: Arc<Mutex<String>>,
-    children: Arc<Mutex<Vec<Entity>>>,
-    components: Arc<HashMap<TypeId, Arc<RwLock<Box<dyn Component>>>>>
+    inner: Arc<EntityInner>
+}
+
+struct EntityInner {
+    membership: RwLock<Option<Membership>>,
+    enabled: Mutex<bool>,
+    invalidated: Mutex<bool>,
+    name: Mutex<String>,
+    // The parent is held weakly, as it holds on to its children already:
+    parent: Mutex<Option<Weak<EntityInner>>>,
+    tags: RwLock<HashSet<Tag>>,
+    children: Mutex<Vec<Entity>>,
+    components: RwLock<HashMap<TypeId, Arc<RwLock<Box<dyn Component>>>>>
+}
+
+// The world an entity has been spawned into, and its id there:
+struct Membership {
+    id: EntityId,
+    registry: EntityRegistry
 }
 
 unsafe impl Send for Entity {}
@@ -30,55 +56,431 @@ unsafe impl Sync for Entity {}
 impl Entity{
     pub fn new(name: &'static str) -> Entity {
         Entity{
-            enabled: Arc::new(Mutex::new(true)),
-            invalidated: Arc::new(Mutex::new(false)),
-            name: Arc::new(Mutex::new(name.to_string())),
-            children: Arc::new(Mutex::new(Vec::new())),
-            components: Arc::new(HashMap::new())
+            inner: Arc::new(EntityInner {
+                membership: RwLock::new(None),
+                enabled: Mutex::new(true),
+                invalidated: Mutex::new(false),
+                name: Mutex::new(name.to_string()),
+                parent: Mutex::new(None),
+                tags: RwLock::new(HashSet::new()),
+                children: Mutex::new(Vec::new()),
+                components: RwLock::new(HashMap::new())
+            })
+        }
+    }
+
+    fn from_inner(inner: Arc<EntityInner>) -> Entity {
+        Entity { inner }
+    }
+
+    fn downgrade(&self) -> Weak<EntityInner> {
+        Arc::downgrade(&self.inner)
+    }
+
+    // Invalid until the entity is spawned into a world:
+    pub fn id(&self) -> EntityId {
+        self.inner
+            .membership
+            .read()
+            .expect("Couldn't read entity id!")
+            .as_ref()
+            .map_or(EntityId::INVALID, |membership| membership.id)
+    }
+
+    // The registry of the world the entity has been spawned into, if any:
+    pub fn registry(&self) -> Option<EntityRegistry> {
+        self.inner
+            .membership
+            .read()
+            .expect("Couldn't read entity registry!")
+            .as_ref()
+            .map(|membership| membership.registry.clone())
+    }
+
+    fn set_membership(&self, membership: Option<Membership>) {
+        *self.inner.membership.write().expect("Couldn't change entity registry!") = membership;
+    }
+
+    // Entities that don't belong to a world have no one to tell:
+    fn emit(&self, event: WorldEvent) {
+        if let Some(registry) = self.registry() {
+            registry.events().emit(event);
+        }
+    }
+
+    // Whether the entity belongs to a world and hasn't been despawned from it:
+    pub fn is_alive(&self) -> bool {
+        self.inner.membership.read().expect("Couldn't read entity id!").is_some()
+    }
+
+    pub fn name(&self) -> String {
+        self.inner.name.lock().expect("Couldn't read name!").clone()
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        *self.inner.enabled.lock().expect("Couldn't read enabled status!")
+    }
+
+    // An entity is only active if all of its ancestors are enabled as well:
+    pub fn is_enabled_in_hierarchy(&self) -> bool {
+        self.is_enabled() && self.parent().map_or(true, |parent| parent.is_enabled_in_hierarchy())
+    }
+
+    pub fn set_enabled(&self, enabled: bool) {
+        let mut current = self.inner.enabled.lock().expect("Couldn't change enabled status!");
+
+        if *current != enabled {
+            *current = enabled;
+            drop(current);
+
+            self.emit(WorldEvent::EntityEnabled(self.id(), enabled));
         }
     }
 
     pub fn component<C: Component>(&self) -> Option<ComponentManager<C>> {
-        self.components
+        self.inner.components
+            .read()
+            .expect("Couldn't read components!")
             .get(&TypeId::of::<C>())
             .map_or(
                 None,
-                |component| Some(ComponentManager::init(component.clone()))
+                |component| Some(ComponentManager::init(self, component.clone()))
             )
     }
+
+    pub fn has_component<C: Component>(&self) -> bool {
+        self.has_component_of_type(TypeId::of::<C>())
+    }
+
+    pub fn has_component_of_type(&self, type_id: TypeId) -> bool {
+        self.inner.components
+            .read()
+            .expect("Couldn't read components!")
+            .contains_key(&type_id)
+    }
+
+    pub fn has_tag<T: Into<Tag>>(&self, tag: T) -> bool {
+        self.inner.tags
+            .read()
+            .expect("Couldn't read tags!")
+            .contains(&tag.into())
+    }
+
+    pub fn add_tag<T: Into<Tag>>(&self, tag: T) {
+        self.inner.tags
+            .write()
+            .expect("Couldn't add tag!")
+            .insert(tag.into());
+    }
+
+    pub fn remove_tag<T: Into<Tag>>(&self, tag: T) -> bool {
+        self.inner.tags
+            .write()
+            .expect("Couldn't remove tag!")
+            .remove(&tag.into())
+    }
+
+    pub fn tags(&self) -> Vec<Tag> {
+        self.inner.tags
+            .read()
+            .expect("Couldn't read tags!")
+            .iter()
+            .cloned()
+            .collect()
+    }
+
+    pub fn insert_component<C: Component>(&self, component: C) -> ComponentManager<C> {
+        let inner: Arc<RwLock<Box<dyn Component>>> = Arc::new(RwLock::new(Box::new(component)));
+
+        self.inner.components
+            .write()
+            .expect("Couldn't insert component!")
+            .insert(TypeId::of::<C>(), inner.clone());
+
+        self.emit(WorldEvent::ComponentAdded(self.id(), TypeId::of::<C>()));
+        ComponentManager::init(self, inner)
+    }
+
+    pub fn remove_component<C: Component>(&self) -> Option<ComponentManager<C>> {
+        let removed = self.inner.components
+            .write()
+            .expect("Couldn't remove component!")
+            .remove(&TypeId::of::<C>());
+
+        if removed.is_some() {
+            self.emit(WorldEvent::ComponentRemoved(self.id(), TypeId::of::<C>()));
+        }
+
+        removed.map(|inner| ComponentManager::init(self, inner))
+    }
+
+    pub fn parent(&self) -> Option<Entity> {
+        self.inner
+            .parent
+            .lock()
+            .expect("Couldn't read parent!")
+            .as_ref()
+            .and_then(Weak::upgrade)
+            .map(Entity::from_inner)
+    }
+
+    // The slash-separated names from the root of the hierarchy down to this entity,
+    // e.g. "RandomTile world/player/upper":
+    pub fn path(&self) -> String {
+        let mut names = vec![self.name()];
+        let mut current = self.parent();
+
+        while let Some(entity) = current {
+            names.push(entity.name());
+            current = entity.parent();
+        }
+
+        names.reverse();
+        names.join("/")
+    }
+
+    pub fn is_descendant_of(&self, ancestor: &Entity) -> bool {
+        let mut current = self.parent();
+
+        while let Some(entity) = current {
+            if entity == *ancestor {
+                return true;
+            }
+
+            current = entity.parent();
+        }
+
+        false
+    }
+
+    // Accumulates the local transforms from the root of the hierarchy down to this entity,
+    // the same way the TranslateSystem does it:
+    pub fn world_transform(&self) -> Transform {
+        let mut lineage = vec![self.clone()];
+        let mut current = self.parent();
+
+        while let Some(entity) = current {
+            current = entity.parent();
+            lineage.push(entity);
+        }
+
+        lineage
+            .iter()
+            .rev()
+            .filter_map(|entity| entity.component::<Transform>())
+            .fold(Transform::new(), |acc, mgr| acc.with_offset(&*mgr.lock_component_for_read()))
+    }
+
+    pub fn detach(&self) {
+        if self.unlink_from_parent() {
+            for entity in self.query_all_entities(true) {
+                entity.emit(WorldEvent::EntityDetached(entity.id()));
+            }
+        }
+    }
+
+    // Returns whether there was a parent to unlink from:
+    fn unlink_from_parent(&self) -> bool {
+        let previous_parent = self.inner.parent
+            .lock()
+            .expect("Couldn't detach entity!")
+            .take()
+            .and_then(|parent| parent.upgrade())
+            .map(Entity::from_inner);
+
+        match previous_parent {
+            Some(previous_parent) => {
+                if let Ok(mut siblings) = previous_parent.inner.children.lock() {
+                    siblings.retain(|sibling| sibling != self);
+                }
+
+                true
+            },
+            None => false
+        }
+    }
+
+    pub fn reparent(&self, new_parent: &Entity, keep_world_transform: bool) -> Result<(), EntityError> {
+        if !self.is_alive() {
+            return Err(EntityError::Despawned(self.id()));
+        }
+
+        if !new_parent.is_alive() {
+            return Err(EntityError::Despawned(new_parent.id()));
+        }
+
+        if new_parent == self || new_parent.is_descendant_of(self) {
+            return Err(EntityError::CyclicHierarchy(self.id(), new_parent.id()));
+        }
+
+        if keep_world_transform {
+            if let Some(mgr) = self.component::<Transform>() {
+                let parent_transform = new_parent.world_transform();
+                let world_transform = self.world_transform();
+
+                *mgr.lock_component_for_write() = world_transform.relative_to(&parent_transform);
+            }
+        }
+
+        new_parent.spawn_entity(self.clone());
+        Ok(())
+    }
+
+    // Deep-copies this entity and its subtree into fresh entities. Components that don't opt in
+    // through Component::duplicate are left out of the copy. The copy has no parent and doesn't
+    // belong to a world until spawned.
+    pub fn duplicate(&self) -> Entity {
+        let copy = Entity::new("Unnamed");
+
+        *copy.inner.name.lock().expect("Couldn't copy name!") = self.name();
+        *copy.inner.enabled.lock().expect("Couldn't copy enabled status!") = self.is_enabled();
+        *copy.inner.tags.write().expect("Couldn't copy tags!") = self.inner.tags.read().expect("Couldn't read tags!").clone();
+
+        if let (Ok(src), Ok(mut dst)) = (self.inner.components.read(), copy.inner.components.write()) {
+            for (type_id, component) in src.iter() {
+                let component = component.read().expect("Couldn't read component!");
+
+                match component.duplicate() {
+                    Some(duplicate) => { dst.insert(*type_id, Arc::new(RwLock::new(duplicate))); },
+                    None => warn!("Entity '{}': {} can't be duplicated and is left out", copy.name(), component.component_name())
+                }
+            }
+        }
+
+        let children: Vec<Entity> = self.inner.children.lock().expect("Couldn't read children!").clone();
+        for child in children {
+            let child_copy = child.duplicate();
+            *child_copy.inner.parent.lock().expect("Couldn't copy child!") = Some(copy.downgrade());
+            copy.inner.children.lock().expect("Couldn't copy child!").push(child_copy);
+        }
+
+        copy
+    }
+
+    pub fn despawn(&self) {
+        self.unlink_from_parent();
+
+        let subtree: Vec<Entity> = self.query_all_entities(true).collect();
+
+        for entity in subtree {
+            let id = entity.id();
+
+            if let Some(registry) = entity.registry() {
+                registry.release(id);
+                registry.events().emit(WorldEvent::EntityDespawned(id));
+            }
+
+            // Dropping the component storage releases everything the components hold on to,
+            // e.g. the model views on the GPU:
+            if let Ok(mut components) = entity.inner.components.write() {
+                components.clear();
+            }
+
+            if let Ok(mut children) = entity.inner.children.lock() {
+                children.clear();
+            }
+        }
+    }
 }
 
 impl Clone for Entity {
     fn clone(&self) -> Self {
         Entity {
-            enabled: self.enabled.clone(),
-            invalidated: self.invalidated.clone(),
-            name: self.name.clone(),
-            children: self.children.clone(),
-            components: self.components.clone()
+            inner: self.inner.clone()
+        }
+    }
+}
+
+// Two entities are the same if they are clones of each other, whether or not they have been
+// spawned into a world:
+impl PartialEq for Entity {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.inner, &other.inner)
+    }
+}
+
+impl Eq for Entity {}
+
+// An entity that is dropped without being despawned gives up its id:
+impl Drop for EntityInner {
+    fn drop(&mut self) {
+        if let Ok(Some(membership)) = self.membership.get_mut().map(Option::take) {
+            membership.registry.release(membership.id);
         }
     }
 }
 
 impl fmt::Debug for Entity {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
-        writeln!(f, "Entity '{}' ({} children) (has component: {:?})", self.name.lock().unwrap(), self.children.lock().unwrap().len(), self.components.keys())
+        writeln!(f, "Entity '{}' [{}] ({} children) (has component: {:?}) (tags: {:?})", self.inner.name.lock().unwrap(), self.id(), self.inner.children.lock().unwrap().len(), self.inner.components.read().unwrap().keys(), self.inner.tags.read().unwrap())
+    }
+}
+
+pub enum EntityError {
+    Despawned(EntityId),
+    CyclicHierarchy(EntityId, EntityId)
+}
+
+impl Error for EntityError {}
+
+impl Display for EntityError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            EntityError::Despawned(id) => write!(f, "Entity {} has been despawned", id),
+            EntityError::CyclicHierarchy(child, parent) => write!(f, "Entity {} can't be moved beneath its own descendant {}", child, parent)
+        }
+    }
+}
+
+impl fmt::Debug for EntityError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "EntityError: {}", self)
     }
 }
 
 pub trait EntityContainer: Into<Entity> + IntoIterator<Item = Entity, IntoIter = EntityIterator> + Sync + Send + Clone {
+    // Depth-first and pre-order unless configured otherwise, e.g.
+    // `container.traverse().breadth_first().max_depth(2)`:
+    fn traverse(&self) -> Traversal{
+        Traversal::new(self.clone().into())
+    }
+
     fn query_entities(&self, include_parent: bool) -> EntityIterator{
-        EntityIterator::new(self.clone().into(), None, include_parent, false)
+        EntityIterator::new(self.traverse().include_root(include_parent))
     }
 
-    // fn query_children : include_parent = false
+    // Like query_entities, but also descends into disabled entities:
+    fn query_all_entities(&self, include_parent: bool) -> EntityIterator{
+        EntityIterator::new(self.traverse().include_root(include_parent).include_disabled(true))
+    }
 
     fn query_direct_children(&self) -> EntityIterator{
-        EntityIterator::new(self.clone().into(), None, false, true)
+        EntityIterator::new(self.traverse().include_root(false).max_depth(1))
+    }
+
+    fn query_entity_by_name(&self, name: &str, include_parent: bool) -> EntityIterator{
+        EntityIterator::new(self.traverse().include_root(include_parent).named(name))
     }
+    // Resolves a path relative to this container, e.g. "player/upper". A segment may contain
+    // `*` and `?` wildcards, and `**` matches any number of levels, e.g. "**/cubeyboi".
+    fn query_path(&self, path: &str) -> Vec<Entity>{
+        let segments: Vec<&str> = path.split('/').filter(|segment| !segment.is_empty()).collect();
+        let mut matches = Vec::new();
 
-    fn query_entity_by_name(&self, name: &'static str, include_parent: bool) -> EntityIterator{
-        EntityIterator::new(self.clone().into(), Some(name.to_string()), include_parent, false)
+        collect_path_matches(&self.clone().into(), &segments, &mut matches);
+
+        let mut visited = HashSet::new();
+        matches.retain(|entity: &Entity| visited.insert(Arc::as_ptr(&entity.inner)));
+        matches
+    }
+    fn query_entities_by_mask(&self, mask: ComponentMask, include_parent: bool) -> MaskedEntityIterator{
+        MaskedEntityIterator::new(self.query_entities(include_parent), mask)
+    }
+    fn query_entities_by_tag<T: Into<Tag>>(&self, tag: T, include_parent: bool) -> MaskedEntityIterator{
+        self.query_entities_by_mask(ComponentMask::tag(tag), include_parent)
+    }
+    fn query<Q: WorldQuery>(&self) -> Query<Q>{
+        Query::new(self.query_entities(true))
     }
     fn query_components<T: Component>(&self, include_parent: bool) -> ComponentIterator<T>{
         ComponentIterator::new(self.clone().into(), include_parent)
@@ -87,52 +489,73 @@ pub trait EntityContainer: Into<Entity> + IntoIterator<Item = Entity, IntoIter =
         FilteredComponentIterator::new(self.clone().into(), filter_predicate, include_parent)
     }
     fn spawn_entity(&self, entity: Entity){
-        let container = self.clone().into();
-        let mut children = container.children.lock().expect("Couldn't spawn child!");
+        let container: Entity = self.clone().into();
+
+        entity.detach();
+        *entity.inner.parent.lock().expect("Couldn't spawn child!") = Some(container.downgrade());
 
-        children.push(entity);
+        container.inner.children.lock().expect("Couldn't spawn child!").push(entity.clone());
+
+        // Entities join the world of the container they are spawned into:
+        if let Some(registry) = container.registry() {
+            registry.adopt(&entity);
+        }
+
+        for spawned in entity.query_all_entities(true) {
+            spawned.emit(WorldEvent::EntitySpawned(spawned.id()));
+        }
     }
 }
 
 impl EntityContainer for Entity {}
 
+fn collect_path_matches(container: &Entity, segments: &[&str], matches: &mut Vec<Entity>) {
+    let (head, tail) = match segments.split_first() {
+        Some(split) => split,
+        None => {
+            matches.push(container.clone());
+            return;
+        }
+    };
+
+    if *head == "**" {
+        collect_path_matches(container, tail, matches);
+
+        for child in container.query_direct_children() {
+            collect_path_matches(&child, segments, matches);
+        }
+    }
+    else {
+        for child in container.query_direct_children() {
+            if glob_match(head, &child.name()) {
+                collect_path_matches(&child, tail, matches);
+            }
+        }
+    }
+}
+
 impl IntoIterator for Entity {
     type Item = Entity;
     type IntoIter = EntityIterator;
 
     fn into_iter(self) -> Self::IntoIter {
-        EntityIterator::new(self.clone(), None, false, false)
+        EntityIterator::new(self.traverse().include_root(false))
     }
 }
 
 pub struct EntityIterator {
-    parent: Entity,
-    yield_parent: bool,
-    first_relatives: Option<Box<dyn Iterator<Item = Entity>>>,
-    relatives_of_child: Option<Box<dyn Iterator<Item = Entity>>>,
-    name_filter: Option<String>,
-    first_relatives_only: bool,
-    first_relative_taken: bool
+    visits: TraversalIterator
 }
 
 impl EntityIterator {
-    pub fn new(parent: Entity, name_filter: Option<String>, include_parent: bool, first_relatives_only: bool) -> Self {
+    pub fn new(traversal: Traversal) -> Self {
         Self {
-            parent: parent.clone(),
-            yield_parent: include_parent,
-            first_relatives: {
-                let children = (*parent.children.lock().unwrap()).clone();
-                Some(Box::new(children.into_iter()))
-            },
-            relatives_of_child: None,
-            name_filter,
-            first_relatives_only,
-            first_relative_taken: false
+            visits: traversal.into_iter()
         }
     }
 
     pub fn reset(&mut self){
-        unimplemented!()
+        self.visits.reset();
     }
 }
 
@@ -140,39 +563,212 @@ impl Iterator for EntityIterator {
     type Item = Entity;
 
     fn next(&mut self) -> Option<Self::Item> {
-        let mut next = None;
+        self.visits.next().map(|visit| visit.entity)
+    }
+}
 
-        if self.yield_parent {
-            next = Some(self.parent.clone());
-            self.yield_parent = false;
-        }
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::entity::builder::EntityBuilder;
+    use crate::world::entity::component::transform::Transform;
+    use crate::world::entity::component::rigid_body::RigidBody;
+    use cgmath::Vector3;
 
-        while next.is_none(){
-            let first_child = self.relatives_of_child.as_mut().map_or(None, |mut iter| iter.next());
+    // A root spawned into a world of its own, so that its entities have ids and events:
+    fn spawned(builder: EntityBuilder) -> (Entity, EntityRegistry) {
+        let registry = EntityRegistry::new();
+        let root = builder.build();
+        registry.adopt(&root);
+        (root, registry)
+    }
 
-            if first_child.is_some() && (!self.first_relatives_only || !self.first_relative_taken) {
-                let first_child = first_child.unwrap();
-                let entity_ok = self.name_filter
-                    .as_ref()
-                    .map_or(
-                        true,
-                        |name_filter| name_filter.eq(first_child.name.lock().unwrap().deref())
-                    );
+    #[test]
+    fn components_can_be_added_and_removed_after_build() {
+        let (entity, registry) = spawned(EntityBuilder::new().with_component(Transform::new()));
+        let mut events = registry.events().subscribe();
 
-                if entity_ok {
-                    next = Some(first_child.clone());
-                    self.first_relative_taken = true;
-                }
-            }
-            else if let Some(second_child) = self.first_relatives.as_mut().unwrap().next() {
-                self.relatives_of_child = Some(Box::new(second_child.clone().query_entities(true)));
-                self.first_relative_taken = false;
-            }
-            else{
-                break;
-            }
+        let body = entity.insert_component(RigidBody::new(2.0));
+        assert!(entity.has_component::<RigidBody>());
+        assert_eq!(entity.component::<RigidBody>().unwrap().lock_component_for_read().mass, 2.0);
+        assert_eq!(body.owner(), entity.id());
+
+        assert!(entity.remove_component::<Transform>().is_some());
+        assert!(entity.remove_component::<Transform>().is_none());
+        assert!(!entity.has_component::<Transform>());
+
+        assert_eq!(events.drain(), Some(vec![
+            WorldEvent::ComponentAdded(entity.id(), TypeId::of::<RigidBody>()),
+            WorldEvent::ComponentRemoved(entity.id(), TypeId::of::<Transform>())
+        ]));
+    }
+
+    #[test]
+    fn reparenting_keeps_the_world_transform() {
+        let (root, _registry) = spawned(EntityBuilder::new()
+            .with_name("root")
+            .with_child(EntityBuilder::new()
+                .with_name("from")
+                .with_component(Transform::new().with_position(Vector3::new(1.0, 2.0, 3.0)))
+                .with_child(EntityBuilder::new()
+                    .with_name("moved")
+                    .with_component(Transform::new().with_position(Vector3::new(0.5, 0.0, 0.0)))))
+            .with_child(EntityBuilder::new()
+                .with_name("to")
+                .with_component(Transform::new().with_position(Vector3::new(-4.0, 0.0, 1.0)))));
+
+        let moved = root.query_path("from/moved").pop().unwrap();
+        let to = root.query_path("to").pop().unwrap();
+        let before = moved.world_transform().position;
+
+        moved.reparent(&to, true).expect("Couldn't reparent");
+
+        assert!(moved.parent().unwrap() == to);
+        assert_eq!(moved.world_transform().position, before);
+        assert_eq!(moved.component::<Transform>().unwrap().lock_component_for_read().position, Vector3::new(5.5, 2.0, 2.0));
+        assert!(root.query_path("from/moved").is_empty());
+
+        // Otherwise the local transform is kept:
+        moved.reparent(&root, false).expect("Couldn't reparent");
+        assert_eq!(moved.world_transform().position, Vector3::new(5.5, 2.0, 2.0));
+    }
+
+    #[test]
+    fn entities_cant_be_moved_beneath_themselves() {
+        let (root, _registry) = spawned(EntityBuilder::new()
+            .with_child(EntityBuilder::new().with_name("child").with_child(EntityBuilder::new().with_name("grandchild"))));
+
+        let child = root.query_path("child").pop().unwrap();
+        let grandchild = root.query_path("child/grandchild").pop().unwrap();
+
+        assert!(matches!(child.reparent(&grandchild, false), Err(EntityError::CyclicHierarchy(_, _))));
+        assert!(matches!(child.reparent(&child, false), Err(EntityError::CyclicHierarchy(_, _))));
+        assert!(grandchild.parent().unwrap() == child);
+    }
+
+    #[test]
+    fn despawning_removes_the_subtree_and_detaching_keeps_it() {
+        let (root, registry) = spawned(EntityBuilder::new()
+            .with_child(EntityBuilder::new().with_name("detached").with_child(EntityBuilder::new()))
+            .with_child(EntityBuilder::new().with_name("despawned").with_child(EntityBuilder::new().with_component(Transform::new()))));
+
+        let detached = root.query_path("detached").pop().unwrap();
+        let despawned = root.query_path("despawned").pop().unwrap();
+        let leaf = despawned.query_direct_children().next().unwrap();
+
+        detached.detach();
+        assert!(detached.parent().is_none());
+        assert!(detached.is_alive() && registry.is_alive(detached.id()));
+        assert_eq!(detached.query_all_entities(true).count(), 2);
+
+        let leaf_id = leaf.id();
+        despawned.despawn();
+        assert!(!despawned.is_alive() && !leaf.is_alive());
+        assert!(registry.lookup(leaf_id).is_none());
+        assert!(!leaf.has_component::<Transform>());
+        assert_eq!(despawned.query_all_entities(true).count(), 1);
+        assert_eq!(root.query_all_entities(true).count(), 1);
+        assert!(matches!(despawned.reparent(&root, false), Err(EntityError::Despawned(_))));
+    }
+
+    #[test]
+    fn disabling_hides_the_subtree() {
+        let (root, registry) = spawned(EntityBuilder::new()
+            .with_child(EntityBuilder::new().with_name("parent")
+                .with_child(EntityBuilder::new().with_name("child").with_component(Transform::new()))));
+        let mut events = registry.events().subscribe();
+
+        let parent = root.query_path("parent").pop().unwrap();
+        let child = root.query_path("parent/child").pop().unwrap();
+
+        parent.set_enabled(false);
+        parent.set_enabled(false);
+        assert!(child.is_enabled() && !child.is_enabled_in_hierarchy());
+        assert_eq!(root.query_entities(false).count(), 0);
+        assert_eq!(root.query_all_entities(false).count(), 2);
+        assert!(root.query::<&Transform>().next().is_none());
+
+        parent.set_enabled(true);
+        assert!(child.is_enabled_in_hierarchy());
+        assert_eq!(root.query_entities(false).count(), 2);
+
+        let transform = child.component::<Transform>().unwrap();
+        transform.set_enabled(false);
+        assert!(!transform.enabled());
+
+        assert_eq!(events.drain(), Some(vec![
+            WorldEvent::EntityEnabled(parent.id(), false),
+            WorldEvent::EntityEnabled(parent.id(), true),
+            WorldEvent::ComponentEnabled(child.id(), TypeId::of::<Transform>(), false)
+        ]));
+    }
+
+    struct Handle {
+        enabled: bool
+    }
+
+    impl Component for Handle {
+        fn enabled(&self) -> bool {
+            self.enabled
         }
 
-        next
+        fn set_enabled(&mut self, enabled: bool) {
+            self.enabled = enabled;
+        }
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn duplicates_are_independent_and_skip_unclonable_components() {
+        let (root, _registry) = spawned(EntityBuilder::new()
+            .with_child(EntityBuilder::new()
+                .with_name("original")
+                .with_tag("obstacle")
+                .with_component(Transform::new().with_position(Vector3::new(1.0, 2.0, 3.0)))
+                .with_component(Handle { enabled: true })
+                .with_child(EntityBuilder::new().with_name("child"))));
+
+        let original = root.query_path("original").pop().unwrap();
+        let copy = original.duplicate();
+
+        assert!(copy != original);
+        assert!(copy.parent().is_none() && !copy.is_alive());
+        assert_eq!(copy.name(), "original");
+        assert!(copy.has_tag("obstacle"));
+        assert!(copy.has_component::<Transform>());
+        assert!(!copy.has_component::<Handle>());
+        assert_eq!(copy.query_path("child").len(), 1);
+        assert!(copy.query_path("child").pop().unwrap().parent().unwrap() == copy);
+
+        copy.component::<Transform>().unwrap().lock_component_for_write().position.x = 10.0;
+        copy.add_tag("copied");
+        assert_eq!(original.component::<Transform>().unwrap().lock_component_for_read().position.x, 1.0);
+        assert!(!original.has_tag("copied"));
+
+        root.spawn_entity(copy.clone());
+        assert!(copy.is_alive() && copy.id() != original.id());
+        assert_eq!(root.query_path("original").len(), 2);
+    }
+
+    #[test]
+    fn paths_glob_and_descend_any_number_of_levels() {
+        let (root, _registry) = spawned(EntityBuilder::new()
+            .with_name("root")
+            .with_child(EntityBuilder::new().with_name("a")
+                .with_child(EntityBuilder::new().with_name("b"))
+                .with_child(EntityBuilder::new().with_name("x")
+                    .with_child(EntityBuilder::new().with_name("b"))
+                    .with_child(EntityBuilder::new().with_name("y")
+                        .with_child(EntityBuilder::new().with_name("b")))))
+            .with_child(EntityBuilder::new().with_name("ab")));
+
+        let paths = |path: &str| root.query_path(path).iter().map(Entity::path).collect::<Vec<_>>();
+
+        assert_eq!(paths("a/**/b"), vec!["root/a/b", "root/a/x/b", "root/a/x/y/b"]);
+        assert_eq!(paths("**/y/b"), vec!["root/a/x/y/b"]);
+        assert_eq!(paths("a/*/b"), vec!["root/a/x/b"]);
+        assert_eq!(paths("a*"), vec!["root/a", "root/ab"]);
+        assert_eq!(paths("/a/?/b"), vec!["root/a/x/b"]);
+        assert_eq!(paths("**/b").len(), 3);
+        assert!(paths("b").is_empty());
+    }
+}
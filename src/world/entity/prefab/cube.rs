@@ -5,7 +5,7 @@ use crate::world::entity::component::model::GraphicsModel;
 use crate::world::entity::builder::EntityBuilder;
 use crate::world::entity::component::transform::Transform;
 use crate::world::entity::component::rigid_body::RigidBody;
-use crate::backend::BackendProxy;
+use crate::world::entity::component::collider::Collider;
 
 pub struct Cube {
     pub pos: Vector3<f32>,
@@ -14,11 +14,12 @@ pub struct Cube {
 }
 
 impl Prefab for Cube {
-    fn apply(&self, builder: EntityBuilder, backend_proxy: &BackendProxy) -> EntityBuilder {
+    fn apply(&self, builder: EntityBuilder) -> EntityBuilder {
         let mut builder = builder
             .with_name("cubeyboi")
             .with_component(Transform::new().with_position(self.pos.clone()))
-            .with_component(GraphicsModel::new("/home/perfah/Programming/kristall/res/model/cube.obj", backend_proxy));
+            .with_component(GraphicsModel::new("/home/perfah/Programming/kristall/res/model/cube.obj"))
+            .with_component(Collider::obb(Vector3 {x: 1.0, y: 1.0, z: 1.0}));
 
         if self.mass > 0.0 {
             builder = builder.
@@ -1,5 +1,4 @@
 use crate::world::entity::builder::EntityBuilder;
-use crate::backend::BackendProxy;
 
 pub mod player;
 pub mod car;
@@ -7,9 +6,9 @@ pub mod cube;
 pub mod rand_tile;
 
 pub trait Prefab {
-    fn instantiate(&self, backend_proxy: &BackendProxy) -> EntityBuilder {
-        self.apply(EntityBuilder::new(), backend_proxy)
+    fn instantiate(&self) -> EntityBuilder {
+        self.apply(EntityBuilder::new())
     }
 
-    fn apply(&self, builder: EntityBuilder, backend_proxy: &BackendProxy) -> EntityBuilder;
+    fn apply(&self, builder: EntityBuilder) -> EntityBuilder;
 }
@@ -9,18 +9,19 @@ use crate::world::entity::component::controller::Controller;
 use crate::backend::input::entity::{WASDEntityController, InputAccelerationMethod};
 use cgmath::Vector3;
 use crate::world::entity::prefab::cube::Cube;
-use crate::backend::BackendProxy;
 use crate::backend::input::camera::MouseCameraController;
 
 pub struct Player {}
 
 impl Prefab for Player {
-    fn apply(&self, builder: EntityBuilder, backend_proxy: &BackendProxy) -> EntityBuilder {
+    fn apply(&self, builder: EntityBuilder) -> EntityBuilder {
         let upper = Cube{ pos: Vector3 {x: 0.0, y: 3.0, z: 0.0}, mass: 0.0, rot: false }
-            .instantiate(backend_proxy);
+            .instantiate()
+            .with_name("upper");
 
         let lower = Cube{ pos: Vector3 {x: 0.0, y: 0.0, z: 0.0}, mass: 0.0, rot: false }
-            .instantiate(backend_proxy);
+            .instantiate()
+            .with_name("lower");
 
         builder
             .with_name("player")
@@ -0,0 +1,264 @@
+use std::marker::PhantomData;
+use crate::world::entity::{Entity, EntityIterator};
+use crate::world::entity::component::{Component, ComponentManager, ComponentReadAccess, ComponentWriteAccess};
+
+// Describes what a query needs from an entity, e.g. `(&Transform, &mut RigidBody)`. Fetching
+// only clones the component managers; nothing is locked until `QueryItem::lock` is called.
+pub trait WorldQuery {
+    type Fetch: Clone + Send + Sync;
+
+    fn fetch(entity: &Entity) -> Option<Self::Fetch>;
+}
+
+pub trait WorldQueryLock<'a>: WorldQuery {
+    type Item;
+
+    fn lock(fetch: &'a Self::Fetch) -> Self::Item;
+}
+
+pub struct With<C: Component>(PhantomData<C>);
+pub struct Without<C: Component>(PhantomData<C>);
+
+impl<'r, C: Component> WorldQuery for &'r C {
+    type Fetch = ComponentManager<C>;
+
+    fn fetch(entity: &Entity) -> Option<Self::Fetch> {
+        entity.component::<C>()
+    }
+}
+
+impl<'a, 'r, C: Component> WorldQueryLock<'a> for &'r C {
+    type Item = ComponentReadAccess<'a, C>;
+
+    fn lock(fetch: &'a Self::Fetch) -> Self::Item {
+        fetch.lock_component_for_read()
+    }
+}
+
+impl<'r, C: Component> WorldQuery for &'r mut C {
+    type Fetch = ComponentManager<C>;
+
+    fn fetch(entity: &Entity) -> Option<Self::Fetch> {
+        entity.component::<C>()
+    }
+}
+
+impl<'a, 'r, C: Component> WorldQueryLock<'a> for &'r mut C {
+    type Item = ComponentWriteAccess<'a, C>;
+
+    fn lock(fetch: &'a Self::Fetch) -> Self::Item {
+        fetch.lock_component_for_write()
+    }
+}
+
+impl<'r, C: Component> WorldQuery for Option<&'r C> {
+    type Fetch = Option<ComponentManager<C>>;
+
+    fn fetch(entity: &Entity) -> Option<Self::Fetch> {
+        Some(entity.component::<C>())
+    }
+}
+
+impl<'a, 'r, C: Component> WorldQueryLock<'a> for Option<&'r C> {
+    type Item = Option<ComponentReadAccess<'a, C>>;
+
+    fn lock(fetch: &'a Self::Fetch) -> Self::Item {
+        fetch.as_ref().map(|mgr| mgr.lock_component_for_read())
+    }
+}
+
+impl<'r, C: Component> WorldQuery for Option<&'r mut C> {
+    type Fetch = Option<ComponentManager<C>>;
+
+    fn fetch(entity: &Entity) -> Option<Self::Fetch> {
+        Some(entity.component::<C>())
+    }
+}
+
+impl<'a, 'r, C: Component> WorldQueryLock<'a> for Option<&'r mut C> {
+    type Item = Option<ComponentWriteAccess<'a, C>>;
+
+    fn lock(fetch: &'a Self::Fetch) -> Self::Item {
+        fetch.as_ref().map(|mgr| mgr.lock_component_for_write())
+    }
+}
+
+impl<C: Component> WorldQuery for With<C> {
+    type Fetch = ();
+
+    fn fetch(entity: &Entity) -> Option<Self::Fetch> {
+        if entity.has_component::<C>() { Some(()) } else { None }
+    }
+}
+
+impl<'a, C: Component> WorldQueryLock<'a> for With<C> {
+    type Item = ();
+
+    fn lock(_: &'a Self::Fetch) -> Self::Item {}
+}
+
+impl<C: Component> WorldQuery for Without<C> {
+    type Fetch = ();
+
+    fn fetch(entity: &Entity) -> Option<Self::Fetch> {
+        if entity.has_component::<C>() { None } else { Some(()) }
+    }
+}
+
+impl<'a, C: Component> WorldQueryLock<'a> for Without<C> {
+    type Item = ();
+
+    fn lock(_: &'a Self::Fetch) -> Self::Item {}
+}
+
+// Components are locked from left to right, so asking for the same component type twice
+// with write access will deadlock.
+macro_rules! impl_world_query_tuple {
+    ($($name:ident),+) => {
+        impl<$($name: WorldQuery),+> WorldQuery for ($($name,)+) {
+            type Fetch = ($($name::Fetch,)+);
+
+            fn fetch(entity: &Entity) -> Option<Self::Fetch> {
+                Some(($($name::fetch(entity)?,)+))
+            }
+        }
+
+        impl<'a, $($name: WorldQueryLock<'a>),+> WorldQueryLock<'a> for ($($name,)+) {
+            type Item = ($($name::Item,)+);
+
+            #[allow(non_snake_case)]
+            fn lock(fetch: &'a Self::Fetch) -> Self::Item {
+                let ($($name,)+) = fetch;
+                ($($name::lock($name),)+)
+            }
+        }
+    }
+}
+
+impl_world_query_tuple!(A);
+impl_world_query_tuple!(A, B);
+impl_world_query_tuple!(A, B, C);
+impl_world_query_tuple!(A, B, C, D);
+impl_world_query_tuple!(A, B, C, D, E);
+impl_world_query_tuple!(A, B, C, D, E, F);
+
+pub struct QueryItem<Q: WorldQuery> {
+    pub entity: Entity,
+    fetch: Q::Fetch
+}
+
+impl<Q: WorldQuery> QueryItem<Q> {
+    pub fn of(entity: &Entity) -> Option<QueryItem<Q>> {
+        Q::fetch(entity).map(|fetch| QueryItem {
+            entity: entity.clone(),
+            fetch
+        })
+    }
+
+    pub fn lock<'a>(&'a self) -> <Q as WorldQueryLock<'a>>::Item where Q: WorldQueryLock<'a> {
+        Q::lock(&self.fetch)
+    }
+
+    pub fn managers(&self) -> &Q::Fetch {
+        &self.fetch
+    }
+}
+
+impl<Q: WorldQuery> Clone for QueryItem<Q> {
+    fn clone(&self) -> Self {
+        QueryItem {
+            entity: self.entity.clone(),
+            fetch: self.fetch.clone()
+        }
+    }
+}
+
+pub struct Query<Q: WorldQuery> {
+    entities: EntityIterator,
+    phantom: PhantomData<fn() -> Q>
+}
+
+impl<Q: WorldQuery> Query<Q> {
+    pub fn new(entities: EntityIterator) -> Self {
+        Self {
+            entities,
+            phantom: PhantomData
+        }
+    }
+}
+
+impl<Q: WorldQuery> Iterator for Query<Q> {
+    type Item = QueryItem<Q>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some(ref entity) = self.entities.next() {
+            if let Some(item) = QueryItem::of(entity) {
+                return Some(item);
+            }
+        }
+
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::entity::EntityContainer;
+    use crate::world::entity::builder::EntityBuilder;
+    use crate::world::entity::component::transform::Transform;
+    use crate::world::entity::component::rigid_body::RigidBody;
+    use crate::world::entity::component::collider::Collider;
+
+    fn world() -> Entity {
+        EntityBuilder::new()
+            .with_name("root")
+            .with_child(EntityBuilder::new().with_name("body").with_component(Transform::new()).with_component(RigidBody::new(1.0)))
+            .with_child(EntityBuilder::new().with_name("static").with_component(Transform::new()).with_component(Collider::sphere(1.0)))
+            .with_child(EntityBuilder::new().with_name("loose").with_component(RigidBody::new(2.0)))
+            .build()
+    }
+
+    fn names<Q: WorldQuery>(query: Query<Q>) -> Vec<String> {
+        query.map(|item| item.entity.name()).collect()
+    }
+
+    #[test]
+    fn tuples_need_every_component() {
+        let world = world();
+
+        assert_eq!(names(world.query::<(&Transform, &mut RigidBody)>()), vec!["body"]);
+        assert_eq!(names(world.query::<&RigidBody>()), vec!["body", "loose"]);
+    }
+
+    #[test]
+    fn optional_components_dont_filter() {
+        let world = world();
+        let items: Vec<_> = world.query::<(&Transform, Option<&RigidBody>)>().collect();
+
+        assert_eq!(items.len(), 2);
+        assert_eq!(items[0].lock().1.map(|body| body.mass), Some(1.0));
+        assert!(items[1].lock().1.is_none());
+    }
+
+    #[test]
+    fn with_and_without_filter_without_fetching() {
+        let world = world();
+
+        assert_eq!(names(world.query::<(&Transform, With<Collider>)>()), vec!["static"]);
+        assert_eq!(names(world.query::<(&Transform, Without<Collider>)>()), vec!["body"]);
+        assert_eq!(names(world.query::<(Without<Transform>, With<RigidBody>)>()), vec!["loose"]);
+    }
+
+    #[test]
+    fn locked_items_write_through() {
+        let world = world();
+
+        for item in world.query::<(&mut RigidBody, Without<Transform>)>() {
+            item.lock().0.mass = 5.0;
+        }
+
+        let masses: Vec<f32> = world.query::<&RigidBody>().map(|item| item.lock().mass).collect();
+        assert_eq!(masses, vec![1.0, 5.0]);
+    }
+}
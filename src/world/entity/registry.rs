@@ -0,0 +1,250 @@
+use std::sync::{Arc, RwLock, Weak};
+use std::fmt;
+use std::fmt::Formatter;
+use crate::world::entity::{Entity, EntityInner, EntityContainer, Membership};
+use crate::world::entity::event::EventBus;
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct EntityId {
+    index: u32,
+    generation: u32
+}
+
+impl EntityId {
+    pub const INVALID: EntityId = EntityId { index: u32::MAX, generation: 0 };
+
+    pub fn index(&self) -> u32 {
+        self.index
+    }
+
+    pub fn generation(&self) -> u32 {
+        self.generation
+    }
+
+    pub fn is_valid(&self) -> bool {
+        self.index != u32::MAX
+    }
+}
+
+impl fmt::Display for EntityId {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}v{}", self.index, self.generation)
+    }
+}
+
+impl fmt::Debug for EntityId {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "EntityId({})", self)
+    }
+}
+
+struct Slot {
+    generation: u32,
+    entity: Option<Weak<EntityInner>>
+}
+
+#[derive(Default)]
+struct Slots {
+    slots: Vec<Slot>,
+    free: Vec<u32>
+}
+
+// Maps generational ids to the entities of one world. A slot is reused after its entity has been
+// released, but with a bumped generation so that old handles no longer resolve. The registry
+// doesn't keep entities alive: an entity that is dropped without being despawned gives up its
+// id as well. Changes to the entities are announced on the registry's event bus.
+#[derive(Clone, Default)]
+pub struct EntityRegistry {
+    inner: Arc<RwLock<Slots>>,
+    events: EventBus
+}
+
+impl EntityRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Registers the entity and its subtree, releasing them from the registry they were in
+    // before, if any. Entities that move here from another registry get new ids.
+    pub fn adopt(&self, entity: &Entity) {
+        for entity in entity.query_all_entities(true) {
+            match entity.registry() {
+                Some(ref registry) if registry == self => continue,
+                Some(registry) => { registry.release(entity.id()); },
+                None => {}
+            }
+
+            let id = self.insert(&entity);
+            entity.set_membership(Some(Membership { id, registry: self.clone() }));
+        }
+    }
+
+    fn insert(&self, entity: &Entity) -> EntityId {
+        let mut inner = self.inner.write().expect("Entity registry poisoned!");
+
+        let index = match inner.free.pop() {
+            Some(index) => index,
+            None => {
+                inner.slots.push(Slot { generation: 0, entity: None });
+                (inner.slots.len() - 1) as u32
+            }
+        };
+
+        let slot = &mut inner.slots[index as usize];
+        slot.entity = Some(entity.downgrade());
+
+        EntityId { index, generation: slot.generation }
+    }
+
+    // The entity keeps its components and children, but no longer belongs to any world:
+    pub fn release(&self, id: EntityId) -> Option<Entity> {
+        let released = {
+            let mut inner = self.inner.write().expect("Entity registry poisoned!");
+            let slot = inner.slots.get_mut(id.index as usize)?;
+
+            if slot.generation != id.generation {
+                return None;
+            }
+
+            let released = slot.entity.take()?;
+            slot.generation = slot.generation.wrapping_add(1);
+            inner.free.push(id.index);
+
+            released
+        };
+
+        // Fails if the entity is being dropped, which is when it releases itself:
+        let entity = released.upgrade().map(Entity::from_inner)?;
+        entity.set_membership(None);
+
+        Some(entity)
+    }
+
+    pub fn lookup(&self, id: EntityId) -> Option<Entity> {
+        self.inner
+            .read()
+            .expect("Entity registry poisoned!")
+            .slots
+            .get(id.index as usize)
+            .filter(|slot| slot.generation == id.generation)
+            .and_then(|slot| slot.entity.as_ref())
+            .and_then(Weak::upgrade)
+            .map(Entity::from_inner)
+    }
+
+    pub fn is_alive(&self, id: EntityId) -> bool {
+        self.lookup(id).is_some()
+    }
+
+    pub fn events(&self) -> &EventBus {
+        &self.events
+    }
+
+    pub fn len(&self) -> usize {
+        let inner = self.inner.read().expect("Entity registry poisoned!");
+        inner.slots.len() - inner.free.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl PartialEq for EntityRegistry {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.inner, &other.inner)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::entity::builder::EntityBuilder;
+    use crate::world::entity::component::transform::Transform;
+    use crate::world::system::members::MemberList;
+
+    fn tree() -> Entity {
+        EntityBuilder::new()
+            .with_name("root")
+            .with_child(EntityBuilder::new().with_name("child"))
+            .build()
+    }
+
+    #[test]
+    fn registries_dont_share_ids() {
+        let (first, second) = (EntityRegistry::new(), EntityRegistry::new());
+        let (a, b) = (tree(), tree());
+
+        first.adopt(&a);
+        second.adopt(&b);
+
+        assert_eq!(a.id(), b.id());
+        assert!(first.lookup(a.id()).unwrap() == a);
+        assert!(second.lookup(b.id()).unwrap() == b);
+        assert_eq!(first.len(), 2);
+    }
+
+    #[test]
+    fn dropped_entities_give_up_their_ids() {
+        let registry = EntityRegistry::new();
+        let root = tree();
+        registry.adopt(&root);
+
+        let child = root.query_direct_children().next().unwrap();
+        let id = child.id();
+        child.detach();
+        assert!(registry.is_alive(id));
+
+        drop(child);
+        assert!(!registry.is_alive(id));
+        assert_eq!(registry.len(), 1);
+
+        drop(root);
+        assert!(registry.is_empty());
+    }
+
+    #[test]
+    fn adopting_moves_entities_between_registries() {
+        let (first, second) = (EntityRegistry::new(), EntityRegistry::new());
+        let root = tree();
+        first.adopt(&root);
+
+        let child = root.query_direct_children().next().unwrap();
+        let old_id = child.id();
+        second.adopt(&child);
+
+        assert!(first.lookup(old_id).is_none());
+        assert!(child.registry().unwrap() == second);
+        assert!(second.lookup(child.id()).unwrap() == child);
+    }
+
+    #[test]
+    fn despawned_entities_are_freed_while_cached() {
+        let registry = EntityRegistry::new();
+        let root = EntityBuilder::new()
+            .with_child(EntityBuilder::new().with_component(Transform::new()))
+            .build();
+        registry.adopt(&root);
+
+        let mut events = registry.events().subscribe();
+        let mut members = MemberList::<&Transform>::new();
+        members.fetch(&root);
+        let manager = members.iter().next().unwrap().managers().clone();
+
+        let child = root.query_direct_children().next().unwrap();
+        let (id, weak) = (child.id(), child.downgrade());
+        child.despawn();
+        drop(child);
+
+        assert!(!registry.is_alive(id));
+        assert_eq!(registry.len(), 1);
+
+        for event in events.drain().unwrap() {
+            members.apply(&event);
+        }
+
+        assert!(members.is_empty());
+        assert!(weak.upgrade().is_none());
+        assert_eq!(manager.owner(), EntityId::INVALID);
+    }
+}
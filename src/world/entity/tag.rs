@@ -0,0 +1,38 @@
+use std::any::{TypeId, type_name};
+use std::fmt;
+use std::fmt::Formatter;
+
+// Tags either carry a free-form label, e.g. "obstacle", or the type of a marker struct, e.g.
+// `Tag::of::<Enemy>()`, which can't be misspelled.
+#[derive(Clone, PartialEq, Eq, Hash)]
+pub enum Tag {
+    Label(String),
+    Typed(TypeId, &'static str)
+}
+
+impl Tag {
+    pub fn of<T: 'static>() -> Tag {
+        Tag::Typed(TypeId::of::<T>(), type_name::<T>())
+    }
+}
+
+impl From<&str> for Tag {
+    fn from(label: &str) -> Self {
+        Tag::Label(label.to_string())
+    }
+}
+
+impl From<String> for Tag {
+    fn from(label: String) -> Self {
+        Tag::Label(label)
+    }
+}
+
+impl fmt::Debug for Tag {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Tag::Label(label) => write!(f, "#{}", label),
+            Tag::Typed(_, name) => write!(f, "#<{}>", name)
+        }
+    }
+}
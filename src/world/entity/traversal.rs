@@ -0,0 +1,317 @@
+use std::collections::VecDeque;
+use crate::world::entity::Entity;
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum TraversalOrder {
+    DepthFirst,
+    BreadthFirst
+}
+
+// Pre-order yields a parent before its children, post-order yields it after them.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum VisitOrder {
+    PreOrder,
+    PostOrder
+}
+
+#[derive(Clone)]
+pub struct Visit {
+    pub entity: Entity,
+    pub depth: usize,
+    pub parent: Option<Entity>
+}
+
+#[derive(Clone)]
+pub struct Traversal {
+    root: Entity,
+    order: TraversalOrder,
+    visit_order: VisitOrder,
+    include_root: bool,
+    include_disabled: bool,
+    max_depth: Option<usize>,
+    name_filter: Option<String>
+}
+
+impl Traversal {
+    pub fn new(root: Entity) -> Self {
+        Self {
+            root,
+            order: TraversalOrder::DepthFirst,
+            visit_order: VisitOrder::PreOrder,
+            include_root: true,
+            include_disabled: false,
+            max_depth: None,
+            name_filter: None
+        }
+    }
+
+    pub fn depth_first(mut self) -> Self {
+        self.order = TraversalOrder::DepthFirst;
+        self
+    }
+
+    pub fn breadth_first(mut self) -> Self {
+        self.order = TraversalOrder::BreadthFirst;
+        self
+    }
+
+    pub fn pre_order(mut self) -> Self {
+        self.visit_order = VisitOrder::PreOrder;
+        self
+    }
+
+    pub fn post_order(mut self) -> Self {
+        self.visit_order = VisitOrder::PostOrder;
+        self
+    }
+
+    pub fn include_root(mut self, include_root: bool) -> Self {
+        self.include_root = include_root;
+        self
+    }
+
+    // A disabled entity hides its whole subtree unless this is set:
+    pub fn include_disabled(mut self, include_disabled: bool) -> Self {
+        self.include_disabled = include_disabled;
+        self
+    }
+
+    // The root is at depth 0, its children at depth 1 and so on:
+    pub fn max_depth(mut self, max_depth: usize) -> Self {
+        self.max_depth = Some(max_depth);
+        self
+    }
+
+    pub fn named(mut self, name: &str) -> Self {
+        self.name_filter = Some(name.to_string());
+        self
+    }
+
+    pub fn iter(&self) -> TraversalIterator {
+        TraversalIterator::new(self.clone())
+    }
+}
+
+impl IntoIterator for Traversal {
+    type Item = Visit;
+    type IntoIter = TraversalIterator;
+
+    fn into_iter(self) -> Self::IntoIter {
+        TraversalIterator::new(self)
+    }
+}
+
+struct Pending {
+    visit: Visit,
+    expanded: bool
+}
+
+pub struct TraversalIterator {
+    traversal: Traversal,
+    pending: VecDeque<Pending>,
+    // Breadth-first post-order can only be produced once the whole tree has been seen:
+    reversed: Option<Vec<Visit>>
+}
+
+impl TraversalIterator {
+    pub fn new(traversal: Traversal) -> Self {
+        let mut iter = Self {
+            traversal,
+            pending: VecDeque::new(),
+            reversed: None
+        };
+
+        iter.reset();
+        iter
+    }
+
+    pub fn traversal(&self) -> &Traversal {
+        &self.traversal
+    }
+
+    pub fn reset(&mut self) {
+        self.pending.clear();
+        self.reversed = None;
+
+        let root = self.traversal.root.clone();
+        if self.is_visible(&root) {
+            self.pending.push_back(Pending {
+                visit: Visit { entity: root, depth: 0, parent: None },
+                expanded: false
+            });
+        }
+
+        if self.traversal.order == TraversalOrder::BreadthFirst && self.traversal.visit_order == VisitOrder::PostOrder {
+            let mut visits = Vec::new();
+            while let Some(visit) = self.next_breadth_first() {
+                visits.push(visit);
+            }
+
+            self.reversed = Some(visits);
+        }
+    }
+
+    fn is_visible(&self, entity: &Entity) -> bool {
+        self.traversal.include_disabled || entity.is_enabled()
+    }
+
+    fn should_yield(&self, visit: &Visit) -> bool {
+        (visit.depth > 0 || self.traversal.include_root) &&
+            self.traversal.name_filter
+                .as_ref()
+                .map_or(true, |name| *name == visit.entity.name())
+    }
+
+    fn children_of(&self, visit: &Visit) -> Vec<Visit> {
+        if self.traversal.max_depth.map_or(false, |max_depth| visit.depth >= max_depth) {
+            return Vec::new();
+        }
+
+        let children = visit.entity.inner.children.lock().expect("Couldn't read children!").clone();
+
+        children
+            .into_iter()
+            .filter(|child| self.is_visible(child))
+            .map(|child| Visit {
+                entity: child,
+                depth: visit.depth + 1,
+                parent: Some(visit.entity.clone())
+            })
+            .collect()
+    }
+
+    fn next_depth_first(&mut self) -> Option<Visit> {
+        while let Some(Pending { visit, expanded }) = self.pending.pop_back() {
+            let post_order = self.traversal.visit_order == VisitOrder::PostOrder;
+
+            if expanded {
+                return Some(visit);
+            }
+
+            let children = self.children_of(&visit);
+            let yielded = self.should_yield(&visit);
+
+            if post_order && yielded {
+                self.pending.push_back(Pending { visit: visit.clone(), expanded: true });
+            }
+
+            // Reversed, so that the first child ends up on top of the stack:
+            for child in children.into_iter().rev() {
+                self.pending.push_back(Pending { visit: child, expanded: false });
+            }
+
+            if !post_order && yielded {
+                return Some(visit);
+            }
+        }
+
+        None
+    }
+
+    fn next_breadth_first(&mut self) -> Option<Visit> {
+        while let Some(Pending { visit, .. }) = self.pending.pop_front() {
+            for child in self.children_of(&visit) {
+                self.pending.push_back(Pending { visit: child, expanded: false });
+            }
+
+            if self.should_yield(&visit) {
+                return Some(visit);
+            }
+        }
+
+        None
+    }
+}
+
+impl Iterator for TraversalIterator {
+    type Item = Visit;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(ref mut reversed) = self.reversed {
+            return reversed.pop();
+        }
+
+        match self.traversal.order {
+            TraversalOrder::DepthFirst => self.next_depth_first(),
+            TraversalOrder::BreadthFirst => self.next_breadth_first()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::entity::EntityContainer;
+    use crate::world::entity::builder::EntityBuilder;
+
+    //      root
+    //     /    \
+    //    a      b
+    //   / \     |
+    //  a1  a2   b1
+    fn tree() -> Entity {
+        EntityBuilder::new()
+            .with_name("root")
+            .with_child(EntityBuilder::new().with_name("a")
+                .with_child(EntityBuilder::new().with_name("a1"))
+                .with_child(EntityBuilder::new().with_name("a2")))
+            .with_child(EntityBuilder::new().with_name("b")
+                .with_child(EntityBuilder::new().with_name("b1")))
+            .build()
+    }
+
+    fn names(traversal: Traversal) -> Vec<String> {
+        traversal.into_iter().map(|visit| visit.entity.name()).collect()
+    }
+
+    #[test]
+    fn depth_first_orders() {
+        let root = tree();
+
+        assert_eq!(names(root.traverse()), vec!["root", "a", "a1", "a2", "b", "b1"]);
+        assert_eq!(names(root.traverse().post_order()), vec!["a1", "a2", "a", "b1", "b", "root"]);
+    }
+
+    #[test]
+    fn breadth_first_orders() {
+        let root = tree();
+
+        assert_eq!(names(root.traverse().breadth_first()), vec!["root", "a", "b", "a1", "a2", "b1"]);
+        assert_eq!(names(root.traverse().breadth_first().post_order()), vec!["b1", "a2", "a1", "b", "a", "root"]);
+    }
+
+    #[test]
+    fn max_depth_counts_from_the_root() {
+        let root = tree();
+
+        assert_eq!(names(root.traverse().max_depth(0)), vec!["root"]);
+        assert_eq!(names(root.traverse().max_depth(1)), vec!["root", "a", "b"]);
+        assert_eq!(names(root.traverse().max_depth(1).post_order()), vec!["a", "b", "root"]);
+        assert_eq!(names(root.traverse().breadth_first().max_depth(1).include_root(false)), vec!["a", "b"]);
+        assert_eq!(names(root.traverse().max_depth(2)).len(), 6);
+    }
+
+    #[test]
+    fn visits_know_their_depth_and_parent() {
+        let root = tree();
+        let visits: Vec<Visit> = root.traverse().breadth_first().into_iter().collect();
+
+        assert_eq!(visits.iter().map(|visit| visit.depth).collect::<Vec<_>>(), vec![0, 1, 1, 2, 2, 2]);
+        assert!(visits[0].parent.is_none());
+        assert!(visits[1].parent.as_ref() == Some(&root));
+        assert_eq!(visits[5].parent.as_ref().map(Entity::name), Some("b".to_string()));
+    }
+
+    #[test]
+    fn iterators_restart_and_skip_disabled_subtrees() {
+        let root = tree();
+        root.query_path("a").pop().unwrap().set_enabled(false);
+
+        let mut iter = root.traverse().post_order().iter();
+        assert_eq!(iter.by_ref().count(), 3);
+        iter.reset();
+        assert_eq!(iter.map(|visit| visit.entity.name()).collect::<Vec<_>>(), vec!["b1", "b", "root"]);
+
+        assert_eq!(names(root.traverse().include_disabled(true)).len(), 6);
+    }
+}
@@ -4,46 +4,218 @@ use std::collections::HashMap;
 use crate::world::entity::component::{Component, ComponentManager, ComponentIterator, FilteredComponentIterator};
 use crate::world::entity::builder::EntityBuilder;
 use crate::world::system::translate::TranslateSystem;
-use crate::world::system::integrate::IntegrateSystem;
+use crate::world::system::integrate::{IntegrateSystem, Integrator};
 use crate::world::system::input::InputSystem;
 use std::thread;
-use crate::world::system::{System, start_system_in_parallel};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+use crate::world::system::System;
+use crate::world::system::scheduler::{Scheduler, ScheduleError, SystemStatus, DEFAULT_TIMESTEP};
 use crate::world::entity::prefab::cube::Cube;
 use crate::world::entity::prefab::Prefab;
-use crate::world::system::gravity::GravitySystem;
+use crate::world::system::gravity::{GravitySystem, Gravity, GravityMode};
+use crate::world::system::snapshot::{SnapshotSystem, TransformSnapshots};
+use crate::world::system::collision::{CollisionSystem, Contacts};
+use crate::world::system::solver::{SolverSystem, SolverSettings};
 use cgmath::Vector3;
-use crate::world::entity::component::camera::Camera;
+use crate::world::entity::component::camera::{Camera, ActiveCamera};
 use crate::world::entity::component::transform::Transform;
 use crate::world::entity::prefab::car::Car;
-use crate::backend::BackendProxy;
+use crate::world::entity::registry::{EntityId, EntityRegistry};
+use crate::world::entity::query::With;
+use crate::world::resource::{Resources, Resource, ResourceManager};
+use crate::util::profiler::Profiler;
+use crate::world::entity::event::Subscription;
+use std::sync::mpsc::{channel, Receiver, RecvTimeoutError};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::error::Error;
+use std::fmt::{self, Display, Formatter};
 
 
 pub mod entity;
 pub mod system;
+pub mod resource;
 
 pub struct World {
     root: Entity,
+    registry: EntityRegistry,
+    resources: Resources,
+    scheduler: Arc<Mutex<Scheduler>>,
+    simulation_thread: Arc<Mutex<Option<SimulationThread>>>
+}
+
+struct SimulationThread {
+    handle: JoinHandle<()>,
+    stop: Arc<AtomicBool>,
+    // Disconnects once the thread has returned:
+    finished: Receiver<()>
 }
 
 impl World {
-    pub fn new<T: Prefab>(prefab: T, backend_proxy: &BackendProxy) -> Self {
-        let world_builder = prefab.instantiate(backend_proxy);
+    pub fn new<T: Prefab>(prefab: T) -> Result<Self, ScheduleError> {
+        let world_builder = prefab.instantiate();
 
         let root = world_builder.build();
+        let registry = EntityRegistry::new();
+        registry.adopt(&root);
+
+        let resources = Resources::new();
+
+        resources.insert(Gravity::default());
+        resources.insert(GravityMode::default());
+        resources.insert(Integrator::default());
+        resources.insert(TransformSnapshots::default());
+        resources.insert(Contacts::default());
+        resources.insert(SolverSettings::default());
+
+        if let Some(camera) = root.query::<(With<Camera>, With<Transform>)>().next() {
+            resources.insert(ActiveCamera(camera.entity.id()));
+        }
+
+        let mut scheduler = Scheduler::new(root.clone(), resources.clone(), DEFAULT_TIMESTEP);
+        scheduler.register::<InputSystem>();
+        scheduler.register::<GravitySystem>();
+        scheduler.register::<IntegrateSystem>();
+        scheduler.register::<CollisionSystem>();
+        scheduler.register::<SolverSystem>();
+        scheduler.register::<TranslateSystem>();
+        scheduler.register::<SnapshotSystem>();
+        scheduler.schedule()?;
+
+        Ok(World {
+            root,
+            registry,
+            resources,
+            scheduler: Arc::new(Mutex::new(scheduler)),
+            simulation_thread: Arc::new(Mutex::new(None))
+        })
+    }
+
+    // Drives the simulation from the caller's loop, e.g. once per rendered frame:
+    pub fn update(&self, elapsed: Duration) -> Result<u32, ScheduleError> {
+        self.scheduler.lock().expect("Scheduler poisoned!").update(elapsed)
+    }
+
+    pub fn step(&self) -> Result<(), ScheduleError> {
+        self.scheduler.lock().expect("Scheduler poisoned!").tick()
+    }
+
+    pub fn timestep(&self) -> Duration {
+        self.scheduler.lock().expect("Scheduler poisoned!").timestep()
+    }
+
+    pub fn set_timestep(&self, timestep: Duration) {
+        self.scheduler.lock().expect("Scheduler poisoned!").set_timestep(timestep);
+    }
+
+    // Drives the simulation from a thread of its own instead, sleeping between ticks. The world
+    // keeps the thread until shutdown, but it stops on its own if the systems can't be scheduled.
+    // Returns false if it is already running.
+    pub fn spawn_simulation_thread(&self) -> bool {
+        let mut simulation_thread = self.simulation_thread.lock().expect("Simulation thread poisoned!");
+        if simulation_thread.is_some() {
+            return false;
+        }
+
+        let world = self.clone();
+        let stop = Arc::new(AtomicBool::new(false));
+        let (finished_sender, finished) = channel::<()>();
+
+        let thread_stop = stop.clone();
+        let handle = thread::spawn(move || {
+            let _finished = finished_sender;
+            let mut prev_time = Instant::now();
+
+            while !thread_stop.load(Ordering::Acquire) {
+                let now = Instant::now();
+                // The scheduler has logged why already:
+                if world.update(now - prev_time).is_err() {
+                    break;
+                }
+                prev_time = now;
+
+                thread::sleep(world.timestep());
+            }
+        });
+
+        *simulation_thread = Some(SimulationThread { handle, stop, finished });
+        true
+    }
+
+    // Returns false if no such system is registered:
+    pub fn stop_system<S>(&self) -> bool where S: for<'a> System<'a> + 'static {
+        self.scheduler.lock().expect("Scheduler poisoned!").stop_system::<S>()
+    }
+
+    pub fn start_system<S>(&self) -> bool where S: for<'a> System<'a> + 'static {
+        self.scheduler.lock().expect("Scheduler poisoned!").start_system::<S>()
+    }
+
+    pub fn profiler(&self) -> Profiler {
+        self.resource::<Profiler>().expect("Missing Profiler resource!").lock_resource_for_read().clone()
+    }
+
+    pub fn system_status(&self) -> Vec<SystemStatus> {
+        self.scheduler.lock().expect("Scheduler poisoned!").system_status()
+    }
+
+    // Stops the simulation thread, if any, waiting at most `timeout` for its current tick to
+    // finish, and then stops all systems. A thread that doesn't finish in time is left behind.
+    pub fn shutdown(&self, timeout: Duration) -> Result<(), ShutdownError> {
+        let simulation_thread = self.simulation_thread
+            .lock()
+            .expect("Simulation thread poisoned!")
+            .take();
+
+        if let Some(SimulationThread { handle, stop, finished }) = simulation_thread {
+            stop.store(true, Ordering::Release);
+
+            match finished.recv_timeout(timeout) {
+                Err(RecvTimeoutError::Timeout) => return Err(ShutdownError::Timeout(timeout)),
+                _ => {
+                    if handle.join().is_err() {
+                        return Err(ShutdownError::Panicked);
+                    }
+                }
+            }
+        }
+
+        self.scheduler.lock().expect("Scheduler poisoned!").shutdown();
+        Ok(())
+    }
+
+    // Receives every structural change made to the world from now on:
+    pub fn subscribe(&self) -> Subscription {
+        self.registry.events().subscribe()
+    }
+
+    pub fn entity(&self, id: EntityId) -> Option<Entity> {
+        self.registry.lookup(id)
+    }
+
+    pub fn resources(&self) -> &Resources {
+        &self.resources
+    }
 
-        start_system_in_parallel::<TranslateSystem, Entity>(root.clone());
-        start_system_in_parallel::<IntegrateSystem, Entity>(root.clone());
-        start_system_in_parallel::<GravitySystem, Entity>(root.clone());
-        start_system_in_parallel::<InputSystem, Entity>(root.clone());
+    pub fn insert_resource<R: Resource>(&self, resource: R) {
+        self.resources.insert(resource);
+    }
 
-        World { root }
+    // Systems wait for the resource while it is locked through the manager, so keep the locks
+    // short lived:
+    pub fn resource<R: Resource>(&self) -> Option<ResourceManager<R>> {
+        self.resources.fetch::<R>()
     }
 }
 
 impl Clone for World {
     fn clone(&self) -> Self {
         Self {
-            root: self.root.clone()
+            root: self.root.clone(),
+            registry: self.registry.clone(),
+            resources: self.resources.clone(),
+            scheduler: self.scheduler.clone(),
+            simulation_thread: self.simulation_thread.clone()
         }
     }
 }
@@ -61,6 +233,84 @@ impl IntoIterator for World {
     type IntoIter = EntityIterator;
 
     fn into_iter(self) -> Self::IntoIter {
-        unimplemented!()
+        self.root.into_iter()
+    }
+}
+
+pub enum ShutdownError {
+    Timeout(Duration),
+    Panicked
+}
+
+impl Error for ShutdownError {}
+
+impl Display for ShutdownError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            ShutdownError::Timeout(timeout) => write!(f, "Simulation thread didn't stop within {:?}", timeout),
+            ShutdownError::Panicked => write!(f, "Simulation thread panicked")
+        }
+    }
+}
+
+impl fmt::Debug for ShutdownError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "ShutdownError: {}", self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::entity::prefab::rand_tile::RandomTile;
+    use crate::world::system::scheduler::{SimulationTime, SystemState};
+    use rand_core::SeedableRng;
+
+    // The bits of every transform in the world, in the order they are found in:
+    fn simulate(seed: u64, ticks: usize) -> Vec<[u32; 6]> {
+        let world = World::new(RandomTile::seed_from_u64(seed)).expect("Couldn't schedule systems!");
+        for _ in 0..ticks {
+            world.step().expect("Couldn't run systems!");
+        }
+
+        world
+            .query::<&Transform>()
+            .map(|item| {
+                let transform = item.lock();
+                let (p, r) = (transform.position, transform.angular_rotation);
+                [p.x.to_bits(), p.y.to_bits(), p.z.to_bits(), r.x.to_bits(), r.y.to_bits(), r.z.to_bits()]
+            })
+            .collect()
+    }
+
+    #[test]
+    fn runs_and_shuts_down_without_a_window() {
+        let world = World::new(RandomTile::seed_from_u64(1)).expect("Couldn't schedule systems!");
+        world.step().expect("Couldn't run systems!");
+
+        assert!(world.spawn_simulation_thread());
+        let start = Instant::now();
+        while world.resource::<SimulationTime>().unwrap().lock_resource_for_read().tick < 10 {
+            assert!(start.elapsed() < Duration::from_secs(10), "Simulation thread doesn't tick");
+            thread::sleep(Duration::from_millis(1));
+        }
+
+        world.shutdown(Duration::from_secs(10)).expect("Couldn't shut down");
+
+        for status in world.system_status() {
+            assert_eq!(status.state, SystemState::Stopped, "{}", status);
+            assert!(status.ticks >= 10, "{}", status);
+            assert_eq!(status.failures, 0, "{}", status);
+        }
+    }
+
+    #[test]
+    fn same_seed_simulates_the_same() {
+        let first = simulate(11, 100);
+
+        assert!(!first.is_empty());
+        assert_ne!(first, simulate(11, 0));
+        assert_eq!(first, simulate(11, 100));
+        assert_ne!(first, simulate(12, 100));
     }
 }
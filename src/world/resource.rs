@@ -0,0 +1,103 @@
+use std::any::{Any, TypeId};
+use std::collections::HashMap;
+use std::collections::hash_map::Entry;
+use std::sync::{Arc, RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+// Anything thread safe can be stored as a world resource; there is at most one value per type.
+pub trait Resource: Any + Send + Sync {}
+
+impl<T: Any + Send + Sync> Resource for T {}
+
+#[derive(Clone)]
+pub struct Resources {
+    inner: Arc<RwLock<HashMap<TypeId, Arc<dyn Any + Send + Sync>>>>
+}
+
+impl Resources {
+    pub fn new() -> Self {
+        Self {
+            inner: Arc::new(RwLock::new(HashMap::new()))
+        }
+    }
+
+    // Replacing a resource updates it in place, so managers fetched earlier see the new value.
+    // Checking for the resource happens under the same lock as adding it, so that two threads
+    // inserting the same type never end up with different managers:
+    pub fn insert<T: Resource>(&self, resource: T) {
+        let existing = match self.inner.write().expect("Couldn't insert resource!").entry(TypeId::of::<T>()) {
+            Entry::Occupied(entry) => entry.get().clone(),
+            Entry::Vacant(entry) => {
+                entry.insert(Arc::new(RwLock::new(resource)));
+                return;
+            }
+        };
+
+        // The map isn't locked anymore by now, so that it stays available while waiting for the
+        // resource itself:
+        if let Ok(inner) = existing.downcast::<RwLock<T>>() {
+            *inner.write().expect("Couldn't insert resource!") = resource;
+        }
+    }
+
+    pub fn remove<T: Resource>(&self) -> bool {
+        self.inner
+            .write()
+            .expect("Couldn't remove resource!")
+            .remove(&TypeId::of::<T>())
+            .is_some()
+    }
+
+    pub fn contains<T: Resource>(&self) -> bool {
+        self.inner
+            .read()
+            .expect("Couldn't read resources!")
+            .contains_key(&TypeId::of::<T>())
+    }
+
+    pub fn fetch<T: Resource>(&self) -> Option<ResourceManager<T>> {
+        let resource = self.inner
+            .read()
+            .expect("Couldn't read resources!")
+            .get(&TypeId::of::<T>())
+            .cloned()?;
+
+        resource
+            .downcast::<RwLock<T>>()
+            .ok()
+            .map(|inner| ResourceManager { inner })
+    }
+}
+
+pub struct ResourceManager<T: Resource> {
+    inner: Arc<RwLock<T>>
+}
+
+impl<T: Resource> Clone for ResourceManager<T> {
+    fn clone(&self) -> Self {
+        ResourceManager {
+            inner: self.inner.clone()
+        }
+    }
+}
+
+impl<T: Resource> ResourceManager<T> {
+    pub fn peek<F, R>(&self, f: F) -> Option<R>
+        where F: FnOnce(&T) -> R {
+
+        self.inner.read().ok().map(|lock| f(&*lock))
+    }
+
+    pub fn peek_mut<F, R>(&self, f: F) -> Option<R>
+        where F: FnOnce(&mut T) -> R {
+
+        self.inner.write().ok().map(|mut lock| f(&mut *lock))
+    }
+
+    pub fn lock_resource_for_read(&self) -> RwLockReadGuard<T> {
+        self.inner.read().unwrap()
+    }
+
+    pub fn lock_resource_for_write(&self) -> RwLockWriteGuard<T> {
+        self.inner.write().unwrap()
+    }
+}
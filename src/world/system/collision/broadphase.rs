@@ -0,0 +1,73 @@
+use std::cmp::Ordering;
+use cgmath::Vector3;
+
+// World space bounding box, used to rule out pairs before the narrowphase looks at them:
+#[derive(Copy, Clone, Debug)]
+pub struct Bounds {
+    pub min: Vector3<f32>,
+    pub max: Vector3<f32>
+}
+
+impl Bounds {
+    pub fn overlaps(&self, other: &Bounds) -> bool {
+        self.min.x <= other.max.x && other.min.x <= self.max.x &&
+            self.min.y <= other.max.y && other.min.y <= self.max.y &&
+            self.min.z <= other.max.z && other.min.z <= self.max.z
+    }
+
+    pub fn center(&self) -> Vector3<f32> {
+        (self.min + self.max) * 0.5
+    }
+}
+
+// Sorts the bounds along the axis they are spread out the most on and sweeps over them, so that
+// only bounds overlapping on that axis are compared. Returns the overlapping pairs as ascending
+// index pairs (i < j), in ascending order.
+pub fn sweep_and_prune(bounds: &[Bounds]) -> Vec<(usize, usize)> {
+    let axis = sweep_axis(bounds);
+
+    let mut order: Vec<usize> = (0..bounds.len()).collect();
+    order.sort_by(|&i, &j| {
+        bounds[i].min[axis]
+            .partial_cmp(&bounds[j].min[axis])
+            .unwrap_or(Ordering::Equal)
+            .then(i.cmp(&j))
+    });
+
+    let mut active: Vec<usize> = Vec::new();
+    let mut pairs = Vec::new();
+
+    for i in order {
+        // Bounds that end before this one starts can't overlap anything further along either:
+        active.retain(|&j| bounds[j].max[axis] >= bounds[i].min[axis]);
+
+        for &j in active.iter() {
+            if bounds[i].overlaps(&bounds[j]) {
+                pairs.push((i.min(j), i.max(j)));
+            }
+        }
+
+        active.push(i);
+    }
+
+    pairs.sort();
+    pairs
+}
+
+// The axis along which the centers vary the most:
+fn sweep_axis(bounds: &[Bounds]) -> usize {
+    if bounds.is_empty() {
+        return 0;
+    }
+
+    let n = bounds.len() as f32;
+    let mean = bounds.iter().map(|b| b.center()).fold(Vector3::new(0.0, 0.0, 0.0), |acc, c| acc + c) / n;
+    let variance = bounds
+        .iter()
+        .map(|b| b.center() - mean)
+        .fold(Vector3::new(0.0, 0.0, 0.0), |acc, d| acc + Vector3::new(d.x * d.x, d.y * d.y, d.z * d.z));
+
+    if variance.x >= variance.y && variance.x >= variance.z { 0 }
+    else if variance.y >= variance.z { 1 }
+    else { 2 }
+}
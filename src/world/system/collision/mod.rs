@@ -0,0 +1,191 @@
+use crate::world::system::{System, SystemRuntimeError, Stage, SystemId, SystemAccess};
+use crate::world::system::integrate::IntegrateSystem;
+use crate::world::system::members::MemberList;
+use crate::world::system::collision::broadphase::{Bounds, sweep_and_prune};
+use crate::world::system::collision::narrowphase::{WorldShape, collide, contact_points};
+use crate::world::resource::{Resources, ResourceManager};
+use crate::world::entity::component::transform::Transform;
+use crate::world::entity::component::rigid_body::RigidBody;
+use crate::world::entity::component::collider::Collider;
+use crate::world::entity::registry::EntityId;
+use crate::world::entity::event::WorldEvent;
+use crate::world::entity::{Entity, EntityContainer};
+use std::time::Duration;
+use cgmath::Vector3;
+use rayon::prelude::*;
+
+pub mod broadphase;
+pub mod narrowphase;
+
+#[derive(Copy, Clone, Debug)]
+pub struct ContactPoint {
+    pub position: Vector3<f32>,
+    pub depth: f32
+}
+
+// How two colliders overlap. The normal points from `a` towards `b`.
+#[derive(Clone, Debug)]
+pub struct ContactManifold {
+    pub a: EntityId,
+    pub b: EntityId,
+    // The entities holding the rigid bodies the colliders move with, if any:
+    pub body_a: Option<EntityId>,
+    pub body_b: Option<EntityId>,
+    pub normal: Vector3<f32>,
+    pub points: Vec<ContactPoint>
+}
+
+impl ContactManifold {
+    pub fn depth(&self) -> f32 {
+        self.points.iter().map(|point| point.depth).fold(0.0, f32::max)
+    }
+}
+
+// World resource with the contacts found during the last tick, ordered by the ids of the
+// colliders involved, first by `a` and then by `b`:
+#[derive(Default)]
+pub struct Contacts {
+    manifolds: Vec<ContactManifold>
+}
+
+impl Contacts {
+    pub fn manifolds(&self) -> &[ContactManifold] {
+        &self.manifolds
+    }
+
+    pub fn involving(&self, id: EntityId) -> impl Iterator<Item = &ContactManifold> {
+        self.manifolds.iter().filter(move |manifold| manifold.a == id || manifold.b == id)
+    }
+
+    pub fn len(&self) -> usize {
+        self.manifolds.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.manifolds.is_empty()
+    }
+}
+
+// A collider as it is placed in the world this tick:
+struct Placement {
+    id: EntityId,
+    body: Option<EntityId>,
+    collider: Collider,
+    transform: Transform
+}
+
+pub struct CollisionSystem {
+    colliders: MemberList<&'static Collider>,
+    contacts: Option<ResourceManager<Contacts>>
+}
+
+impl<'a> System<'a> for CollisionSystem {
+    type Environment = (&'a MemberList<&'static Collider>, &'a ResourceManager<Contacts>);
+
+    fn new() -> Self {
+        Self {
+            colliders: MemberList::new(),
+            contacts: None
+        }
+    }
+
+    fn stage() -> Stage {
+        Stage::Simulate
+    }
+
+    // Contacts are found where the bodies have just been moved to:
+    fn after() -> Vec<SystemId> {
+        vec![SystemId::of::<IntegrateSystem>()]
+    }
+
+    fn access() -> SystemAccess {
+        SystemAccess::new()
+            .read::<Collider>()
+            .read::<Transform>()
+            .read::<RigidBody>()
+            .write::<Contacts>()
+    }
+
+    fn on_fetch<T: EntityContainer>(&mut self, source: &T, resources: &Resources) -> Result<(), SystemRuntimeError> {
+        self.contacts = resources.fetch::<Contacts>();
+        self.colliders.fetch(source);
+        Result::Ok(())
+    }
+
+    fn on_event(&mut self, event: &WorldEvent) -> bool {
+        self.colliders.apply(event)
+    }
+
+    fn on_freeze(&'a self) -> Result<Self::Environment, SystemRuntimeError> {
+        match self.contacts {
+            Some(ref contacts) => Result::Ok((&self.colliders, contacts)),
+            None => Result::Err(SystemRuntimeError::new("Missing Contacts resource"))
+        }
+    }
+
+    fn on_run(&self, (colliders, contacts): Self::Environment, _delta: Duration) {
+        let placements: Vec<Placement> = colliders
+            .iter()
+            .filter_map(|item| {
+                let collider = (*item.lock()).clone();
+                if !collider.enabled { return None; }
+
+                Some(Placement {
+                    id: item.entity.id(),
+                    body: body_of(&item.entity).map(|body| body.id()),
+                    collider,
+                    transform: item.entity.world_transform()
+                })
+            })
+            .collect();
+
+        let shapes: Vec<WorldShape> = placements
+            .iter()
+            .map(|placement| WorldShape::new(&placement.collider, &placement.transform))
+            .collect();
+
+        let bounds: Vec<Bounds> = shapes.iter().map(|shape| shape.bounds()).collect();
+
+        let mut manifolds: Vec<ContactManifold> = sweep_and_prune(&bounds)
+            .into_par_iter()
+            .filter(|&(i, j)| {
+                let (body_i, body_j) = (placements[i].body, placements[j].body);
+
+                // Colliders of the same body don't collide, and neither does static geometry:
+                body_i != body_j && (body_i.is_some() || body_j.is_some())
+            })
+            .filter_map(|(i, j)| {
+                let contact = collide(&shapes[i], &shapes[j])?;
+
+                Some(ContactManifold {
+                    a: placements[i].id,
+                    b: placements[j].id,
+                    body_a: placements[i].body,
+                    body_b: placements[j].body,
+                    normal: contact.normal,
+                    points: contact_points(&shapes[i], &shapes[j], &contact)
+                })
+            })
+            .collect();
+
+        // The order of the members depends on the order they were spawned and fetched in:
+        manifolds.sort_by_key(|manifold| (manifold.a, manifold.b));
+
+        contacts.lock_resource_for_write().manifolds = manifolds;
+    }
+}
+
+// The closest entity up the hierarchy that has a rigid body, which the collider moves with:
+pub fn body_of(entity: &Entity) -> Option<Entity> {
+    let mut current = Some(entity.clone());
+
+    while let Some(entity) = current {
+        if entity.component::<RigidBody>().is_some() {
+            return Some(entity);
+        }
+
+        current = entity.parent();
+    }
+
+    None
+}
@@ -0,0 +1,655 @@
+use crate::world::entity::component::collider::{Collider, Shape};
+use crate::world::entity::component::transform::Transform;
+use crate::world::system::collision::broadphase::Bounds;
+use crate::world::system::collision::ContactPoint;
+use cgmath::{Vector3, Matrix3, Matrix, InnerSpace, SquareMatrix, ElementWise, Rad};
+
+const GJK_MAX_ITERATIONS: usize = 64;
+const EPA_MAX_ITERATIONS: usize = 64;
+// How close EPA has to get to the boundary of the Minkowski difference before it stops:
+const EPA_TOLERANCE: f32 = 0.0001;
+const EPSILON: f32 = 0.000001;
+// How closely a face of a box has to line up with the contact normal for the contact to be taken
+// as resting on that face, rather than on an edge or a corner:
+const FACE_CONTACT_ALIGNMENT: f32 = 0.95;
+// Enough for a box to rest stably on a face:
+const MAX_MANIFOLD_POINTS: usize = 4;
+
+// A collider placed in the world:
+pub struct WorldShape<'a> {
+    shape: &'a Shape,
+    center: Vector3<f32>,
+    rotation: Matrix3<f32>,
+    scale: Vector3<f32>
+}
+
+impl<'a> WorldShape<'a> {
+    // Rotations are applied in the same order as the renderer applies them, after scaling:
+    pub fn new(collider: &'a Collider, transform: &Transform) -> Self {
+        let angular_rotation = transform.angular_rotation;
+        let rotation = match collider.shape {
+            Shape::Aabb { .. } => Matrix3::identity(),
+            _ => Matrix3::from_angle_x(Rad(angular_rotation.x)) *
+                Matrix3::from_angle_y(Rad(angular_rotation.y)) *
+                Matrix3::from_angle_z(Rad(angular_rotation.z))
+        };
+
+        WorldShape {
+            shape: &collider.shape,
+            center: transform.position + rotation * collider.offset.mul_element_wise(transform.scale),
+            rotation,
+            scale: transform.scale
+        }
+    }
+
+    pub fn center(&self) -> Vector3<f32> {
+        self.center
+    }
+
+    // The point of the shape furthest along the direction, in world space. Scaling and rotating
+    // the shape is a linear map, and the support of a mapped shape is the mapped support along
+    // the direction mapped back by the transpose:
+    pub fn support(&self, direction: Vector3<f32>) -> Vector3<f32> {
+        let local_direction = (self.rotation.transpose() * direction).mul_element_wise(self.scale);
+        self.center + self.rotation * self.shape.support(local_direction).mul_element_wise(self.scale)
+    }
+
+    // The radius of a sphere that is scaled the same along every axis, which is still a sphere:
+    fn sphere_radius(&self) -> Option<f32> {
+        match *self.shape {
+            Shape::Sphere { radius } if self.scale.x.abs() == self.scale.y.abs() && self.scale.y.abs() == self.scale.z.abs() =>
+                Some(radius * self.scale.x.abs()),
+            _ => None
+        }
+    }
+
+    // The axes and half extents of a box, in world space:
+    fn oriented_box(&self) -> Option<([Vector3<f32>; 3], Vector3<f32>)> {
+        match *self.shape {
+            Shape::Aabb { half_extents } | Shape::Obb { half_extents } => Some((
+                [self.rotation.x, self.rotation.y, self.rotation.z],
+                half_extents.mul_element_wise(Vector3::new(self.scale.x.abs(), self.scale.y.abs(), self.scale.z.abs()))
+            )),
+            _ => None
+        }
+    }
+
+    pub fn bounds(&self) -> Bounds {
+        let axes = [Vector3::unit_x(), Vector3::unit_y(), Vector3::unit_z()];
+        let mut bounds = Bounds { min: self.center, max: self.center };
+
+        for (i, axis) in axes.iter().enumerate() {
+            bounds.min[i] = self.support(-*axis)[i];
+            bounds.max[i] = self.support(*axis)[i];
+        }
+
+        bounds
+    }
+}
+
+// Where two shapes touch. The normal points from the first shape towards the second, and moving
+// them apart by the depth along it separates them.
+#[derive(Copy, Clone, Debug)]
+pub struct Contact {
+    pub normal: Vector3<f32>,
+    pub depth: f32,
+    // Halfway between the deepest points of either shape:
+    pub position: Vector3<f32>
+}
+
+pub fn collide(a: &WorldShape, b: &WorldShape) -> Option<Contact> {
+    match (a.sphere_radius(), b.sphere_radius()) {
+        (Some(radius_a), Some(radius_b)) => collide_spheres(a.center, radius_a, b.center, radius_b),
+        _ => gjk(a, b).and_then(|simplex| epa(a, b, simplex))
+    }
+}
+
+// Curved shapes converge slowly in EPA, and spheres are common enough to deserve a shortcut:
+fn collide_spheres(center_a: Vector3<f32>, radius_a: f32, center_b: Vector3<f32>, radius_b: f32) -> Option<Contact> {
+    let distance_vector = center_b - center_a;
+    let distance = distance_vector.magnitude();
+    let depth = radius_a + radius_b - distance;
+
+    if depth <= 0.0 {
+        return None;
+    }
+
+    // Concentric spheres have no preferred direction to separate in:
+    let normal = if distance > EPSILON { distance_vector / distance } else { Vector3::unit_y() };
+    let deepest_a = center_a + normal * radius_a;
+    let deepest_b = center_b - normal * radius_b;
+
+    Some(Contact { normal, depth, position: (deepest_a + deepest_b) * 0.5 })
+}
+
+// A point of the Minkowski difference a - b, along with the points of the shapes it came from:
+#[derive(Copy, Clone, Debug)]
+struct SupportPoint {
+    point: Vector3<f32>,
+    on_a: Vector3<f32>,
+    on_b: Vector3<f32>
+}
+
+fn support(a: &WorldShape, b: &WorldShape, direction: Vector3<f32>) -> SupportPoint {
+    let on_a = a.support(direction);
+    let on_b = b.support(-direction);
+
+    SupportPoint { point: on_a - on_b, on_a, on_b }
+}
+
+fn same_direction(direction: Vector3<f32>, towards: Vector3<f32>) -> bool {
+    direction.dot(towards) > 0.0
+}
+
+// Looks for a tetrahedron within the Minkowski difference that encloses the origin, which exists
+// only if the shapes overlap. The newest point of the simplex is kept first.
+fn gjk(a: &WorldShape, b: &WorldShape) -> Option<Vec<SupportPoint>> {
+    let mut direction = b.center() - a.center();
+    if direction.magnitude2() < EPSILON {
+        direction = Vector3::unit_x();
+    }
+
+    let first = support(a, b, direction);
+    let mut simplex = vec![first];
+    direction = -first.point;
+
+    for _ in 0..GJK_MAX_ITERATIONS {
+        // The origin is on the boundary, so the shapes merely touch:
+        if direction.magnitude2() < EPSILON {
+            return None;
+        }
+
+        let next = support(a, b, direction);
+        if next.point.dot(direction) <= 0.0 {
+            return None;
+        }
+
+        simplex.insert(0, next);
+        if next_simplex(&mut simplex, &mut direction) {
+            return Some(simplex);
+        }
+    }
+
+    None
+}
+
+// Reduces the simplex to the feature closest to the origin and points the direction at the
+// origin from there. Returns true once the simplex encloses the origin.
+fn next_simplex(simplex: &mut Vec<SupportPoint>, direction: &mut Vector3<f32>) -> bool {
+    match simplex.len() {
+        2 => line(simplex, direction),
+        3 => triangle(simplex, direction),
+        _ => tetrahedron(simplex, direction)
+    }
+}
+
+fn line(simplex: &mut Vec<SupportPoint>, direction: &mut Vector3<f32>) -> bool {
+    let (a, b) = (simplex[0], simplex[1]);
+    let ab = b.point - a.point;
+    let ao = -a.point;
+
+    if same_direction(ab, ao) {
+        *direction = ab.cross(ao).cross(ab);
+
+        // The origin is on the line itself, so any direction perpendicular to it will do:
+        if direction.magnitude2() < EPSILON {
+            *direction = ab.cross(Vector3::unit_x());
+            if direction.magnitude2() < EPSILON {
+                *direction = ab.cross(Vector3::unit_y());
+            }
+        }
+    }
+    else {
+        *simplex = vec![a];
+        *direction = ao;
+    }
+
+    false
+}
+
+fn triangle(simplex: &mut Vec<SupportPoint>, direction: &mut Vector3<f32>) -> bool {
+    let (a, b, c) = (simplex[0], simplex[1], simplex[2]);
+    let ab = b.point - a.point;
+    let ac = c.point - a.point;
+    let ao = -a.point;
+    let abc = ab.cross(ac);
+
+    if same_direction(abc.cross(ac), ao) {
+        if same_direction(ac, ao) {
+            *simplex = vec![a, c];
+            *direction = ac.cross(ao).cross(ac);
+            false
+        }
+        else {
+            *simplex = vec![a, b];
+            line(simplex, direction)
+        }
+    }
+    else if same_direction(ab.cross(abc), ao) {
+        *simplex = vec![a, b];
+        line(simplex, direction)
+    }
+    else if same_direction(abc, ao) {
+        *direction = abc;
+        false
+    }
+    else {
+        *simplex = vec![a, c, b];
+        *direction = -abc;
+        false
+    }
+}
+
+fn tetrahedron(simplex: &mut Vec<SupportPoint>, direction: &mut Vector3<f32>) -> bool {
+    let (a, b, c, d) = (simplex[0], simplex[1], simplex[2], simplex[3]);
+    let ab = b.point - a.point;
+    let ac = c.point - a.point;
+    let ad = d.point - a.point;
+    let ao = -a.point;
+
+    if same_direction(ab.cross(ac), ao) {
+        *simplex = vec![a, b, c];
+        triangle(simplex, direction)
+    }
+    else if same_direction(ac.cross(ad), ao) {
+        *simplex = vec![a, c, d];
+        triangle(simplex, direction)
+    }
+    else if same_direction(ad.cross(ab), ao) {
+        *simplex = vec![a, d, b];
+        triangle(simplex, direction)
+    }
+    else {
+        true
+    }
+}
+
+// A face of the EPA polytope, with its outward normal and its distance from the origin:
+struct Face {
+    indices: [usize; 3],
+    normal: Vector3<f32>,
+    distance: f32
+}
+
+impl Face {
+    // Returns None for degenerate faces, which have no normal:
+    fn new(polytope: &[SupportPoint], indices: [usize; 3]) -> Option<Face> {
+        let a = polytope[indices[0]].point;
+        let b = polytope[indices[1]].point;
+        let c = polytope[indices[2]].point;
+
+        let normal = (b - a).cross(c - a);
+        if normal.magnitude2() < EPSILON * EPSILON {
+            return None;
+        }
+
+        let normal = normal.normalize();
+        let distance = normal.dot(a);
+
+        // The origin is inside the polytope, so outward normals point away from it:
+        Some(if distance < 0.0 {
+            Face { indices: [indices[0], indices[2], indices[1]], normal: -normal, distance: -distance }
+        } else {
+            Face { indices, normal, distance }
+        })
+    }
+}
+
+// Expands the enclosing tetrahedron towards the boundary of the Minkowski difference until the
+// face closest to the origin is on it. That face tells how far and in which direction the shapes
+// have to move apart.
+fn epa(a: &WorldShape, b: &WorldShape, simplex: Vec<SupportPoint>) -> Option<Contact> {
+    let mut polytope = simplex;
+    let mut faces: Vec<Face> = [[0, 1, 2], [0, 3, 1], [0, 2, 3], [1, 3, 2]]
+        .iter()
+        .filter_map(|indices| Face::new(&polytope, *indices))
+        .collect();
+
+    for _ in 0..EPA_MAX_ITERATIONS {
+        let closest = closest_face(&faces)?;
+        let normal = faces[closest].normal;
+        let distance = faces[closest].distance;
+
+        let next = support(a, b, normal);
+        if next.point.dot(normal) - distance < EPA_TOLERANCE {
+            break;
+        }
+
+        // Remove every face the new point can see, remembering the edges of the hole that leaves:
+        let mut edges: Vec<(usize, usize)> = Vec::new();
+        faces.retain(|face| {
+            if !same_direction(face.normal, next.point - polytope[face.indices[0]].point) {
+                return true;
+            }
+
+            for k in 0..3 {
+                let edge = (face.indices[k], face.indices[(k + 1) % 3]);
+
+                // Edges shared by two removed faces are inside the hole:
+                match edges.iter().position(|&(from, to)| from == edge.1 && to == edge.0) {
+                    Some(index) => { edges.remove(index); },
+                    None => edges.push(edge)
+                }
+            }
+
+            false
+        });
+
+        polytope.push(next);
+        let new_index = polytope.len() - 1;
+
+        for (from, to) in edges {
+            if let Some(face) = Face::new(&polytope, [from, to, new_index]) {
+                faces.push(face);
+            }
+        }
+    }
+
+    let face = &faces[closest_face(&faces)?];
+    let (on_a, on_b) = witness_points(&polytope, face);
+
+    Some(Contact {
+        normal: face.normal,
+        depth: face.distance,
+        position: (on_a + on_b) * 0.5
+    })
+}
+
+fn closest_face(faces: &[Face]) -> Option<usize> {
+    faces
+        .iter()
+        .enumerate()
+        .min_by(|(_, f1), (_, f2)| f1.distance.partial_cmp(&f2.distance).unwrap_or(std::cmp::Ordering::Equal))
+        .map(|(index, _)| index)
+}
+
+// Projects the origin onto the face and carries its barycentric coordinates over to the points
+// of either shape that make up the face:
+fn witness_points(polytope: &[SupportPoint], face: &Face) -> (Vector3<f32>, Vector3<f32>) {
+    let [i, j, k] = face.indices;
+    let (a, b, c) = (polytope[i], polytope[j], polytope[k]);
+    let p = face.normal * face.distance;
+
+    let v0 = b.point - a.point;
+    let v1 = c.point - a.point;
+    let v2 = p - a.point;
+
+    let d00 = v0.dot(v0);
+    let d01 = v0.dot(v1);
+    let d11 = v1.dot(v1);
+    let d20 = v2.dot(v0);
+    let d21 = v2.dot(v1);
+    let denominator = d00 * d11 - d01 * d01;
+
+    let (u, v, w) = if denominator.abs() < EPSILON {
+        (1.0, 0.0, 0.0)
+    } else {
+        let v = (d11 * d20 - d01 * d21) / denominator;
+        let w = (d00 * d21 - d01 * d20) / denominator;
+        (1.0 - v - w, v, w)
+    };
+
+    (
+        a.on_a * u + b.on_a * v + c.on_a * w,
+        a.on_b * u + b.on_b * v + c.on_b * w
+    )
+}
+
+// A face of a box, spanned by two axes from its center:
+#[derive(Copy, Clone)]
+struct BoxFace {
+    center: Vector3<f32>,
+    normal: Vector3<f32>,
+    tangents: [Vector3<f32>; 2],
+    extents: [f32; 2]
+}
+
+impl BoxFace {
+    // The face whose outward normal points the most along the direction, along with how well
+    // the two line up:
+    fn facing(center: Vector3<f32>, axes: &[Vector3<f32>; 3], half_extents: Vector3<f32>, direction: Vector3<f32>) -> (BoxFace, f32) {
+        let (axis, alignment) = (0..3)
+            .map(|i| (i, axes[i].dot(direction)))
+            .fold((0, 0.0f32), |best, (i, alignment)| if alignment.abs() > best.1.abs() { (i, alignment) } else { best });
+
+        let (u, v) = ((axis + 1) % 3, (axis + 2) % 3);
+        let normal = axes[axis] * alignment.signum();
+
+        let face = BoxFace {
+            center: center + normal * half_extents[axis],
+            normal,
+            tangents: [axes[u], axes[v]],
+            extents: [half_extents[u], half_extents[v]]
+        };
+
+        (face, alignment.abs())
+    }
+
+    // Going around the face:
+    fn corners(&self) -> Vec<Vector3<f32>> {
+        let (u, v) = (self.tangents[0] * self.extents[0], self.tangents[1] * self.extents[1]);
+        vec![self.center + u + v, self.center - u + v, self.center - u - v, self.center + u - v]
+    }
+}
+
+// The points the solver pushes the shapes apart at. EPA only finds the single deepest point, on
+// which a box resting on a face would keep tipping over, so for two boxes in face contact, the
+// face of one box (the incident face) is clipped against the face of the other (the reference
+// face) that is closest to the contact normal.
+pub fn contact_points(a: &WorldShape, b: &WorldShape, contact: &Contact) -> Vec<ContactPoint> {
+    let deepest = vec![ContactPoint { position: contact.position, depth: contact.depth }];
+
+    let ((axes_a, half_extents_a), (axes_b, half_extents_b)) = match (a.oriented_box(), b.oriented_box()) {
+        (Some(box_a), Some(box_b)) => (box_a, box_b),
+        _ => return deepest
+    };
+
+    let (face_a, alignment_a) = BoxFace::facing(a.center, &axes_a, half_extents_a, contact.normal);
+    let (face_b, alignment_b) = BoxFace::facing(b.center, &axes_b, half_extents_b, -contact.normal);
+
+    if alignment_a.max(alignment_b) < FACE_CONTACT_ALIGNMENT {
+        return deepest;
+    }
+
+    // Slightly favoring a keeps the reference face from flipping between ticks:
+    let (reference, incident) = if alignment_a + 0.001 >= alignment_b {
+        (face_a, BoxFace::facing(b.center, &axes_b, half_extents_b, -face_a.normal).0)
+    } else {
+        (face_b, BoxFace::facing(a.center, &axes_a, half_extents_a, -face_b.normal).0)
+    };
+
+    let mut polygon = incident.corners();
+    for (tangent, extent) in reference.tangents.iter().zip(reference.extents.iter()) {
+        let offset = tangent.dot(reference.center);
+        polygon = clip(&polygon, *tangent, offset + extent);
+        polygon = clip(&polygon, -*tangent, -offset + extent);
+    }
+
+    // Of what is left, whatever is below the reference face touches. The point of contact is
+    // halfway between the incident point and the reference face:
+    let points: Vec<ContactPoint> = polygon
+        .into_iter()
+        .filter_map(|point| {
+            let separation = reference.normal.dot(point - reference.center);
+            if separation > 0.0 {
+                return None;
+            }
+
+            Some(ContactPoint { position: point - reference.normal * (separation * 0.5), depth: -separation })
+        })
+        .collect();
+
+    if points.is_empty() {
+        deepest
+    } else {
+        reduce(points, reference.normal)
+    }
+}
+
+// Keeps the part of the polygon where the dot product with the normal is at most the offset:
+fn clip(polygon: &[Vector3<f32>], normal: Vector3<f32>, offset: f32) -> Vec<Vector3<f32>> {
+    let mut clipped = Vec::with_capacity(polygon.len() + 1);
+
+    for (i, &from) in polygon.iter().enumerate() {
+        let to = polygon[(i + 1) % polygon.len()];
+        let (distance_from, distance_to) = (normal.dot(from) - offset, normal.dot(to) - offset);
+
+        if distance_from <= 0.0 {
+            clipped.push(from);
+        }
+
+        // The edge crosses the plane:
+        if (distance_from <= 0.0) != (distance_to <= 0.0) {
+            clipped.push(from + (to - from) * (distance_from / (distance_from - distance_to)));
+        }
+    }
+
+    clipped
+}
+
+// Cuts the points down to the deepest one and those spanning the largest area with it:
+fn reduce(mut points: Vec<ContactPoint>, normal: Vector3<f32>) -> Vec<ContactPoint> {
+    if points.len() <= MAX_MANIFOLD_POINTS {
+        return points;
+    }
+
+    let first = take_max(&mut points, |point| point.depth).unwrap();
+    let second = take_max(&mut points, |point| (point.position - first.position).magnitude2()).unwrap();
+    let mut kept = vec![first, second];
+
+    // The points furthest away from the line through the first two, on either side of it:
+    let edge = second.position - first.position;
+    let side = |point: &ContactPoint| edge.cross(point.position - first.position).dot(normal);
+
+    for &sign in [1.0, -1.0].iter() {
+        if let Some(point) = take_max(&mut points, |point| side(point) * sign) {
+            if side(&point) * sign > 0.0 {
+                kept.push(point);
+            }
+        }
+    }
+
+    kept
+}
+
+fn take_max<F>(points: &mut Vec<ContactPoint>, key: F) -> Option<ContactPoint> where F: Fn(&ContactPoint) -> f32 {
+    let index = (0..points.len())
+        .max_by(|&i, &j| key(&points[i]).partial_cmp(&key(&points[j])).unwrap_or(std::cmp::Ordering::Equal))?;
+
+    Some(points.remove(index))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f32::consts::FRAC_PI_4;
+
+    fn placed(position: Vector3<f32>, angular_rotation: Vector3<f32>) -> Transform {
+        let mut transform = Transform::new().with_position(position);
+        transform.angular_rotation = angular_rotation;
+        transform
+    }
+
+    fn at(position: Vector3<f32>) -> Transform {
+        placed(position, Vector3::new(0.0, 0.0, 0.0))
+    }
+
+    fn assert_close(actual: Vector3<f32>, expected: Vector3<f32>, tolerance: f32) {
+        assert!((actual - expected).magnitude() < tolerance, "{:?} != {:?}", actual, expected);
+    }
+
+    #[test]
+    fn sphere_overlaps_box() {
+        let (sphere, cube) = (Collider::sphere(1.0), Collider::obb(Vector3::new(1.0, 1.0, 1.0)));
+        let a = WorldShape::new(&cube, &at(Vector3::new(0.0, 0.0, 0.0)));
+        let b = WorldShape::new(&sphere, &at(Vector3::new(0.0, 1.75, 0.0)));
+
+        let contact = collide(&a, &b).expect("Shapes overlap");
+        assert!((contact.depth - 0.25).abs() < 0.01, "Depth {}", contact.depth);
+        assert_close(contact.normal, Vector3::new(0.0, 1.0, 0.0), 0.01);
+
+        let far = WorldShape::new(&sphere, &at(Vector3::new(0.0, 2.25, 0.0)));
+        assert!(collide(&a, &far).is_none());
+    }
+
+    #[test]
+    fn boxes_overlap() {
+        let cube = Collider::obb(Vector3::new(1.0, 1.0, 1.0));
+        let a = WorldShape::new(&cube, &at(Vector3::new(0.0, 0.0, 0.0)));
+        let b = WorldShape::new(&cube, &at(Vector3::new(1.5, 0.2, 0.1)));
+
+        let contact = collide(&a, &b).expect("Shapes overlap");
+        assert!((contact.depth - 0.5).abs() < 0.001, "Depth {}", contact.depth);
+        assert_close(contact.normal, Vector3::new(1.0, 0.0, 0.0), 0.001);
+
+        // The normal always points from the first shape towards the second:
+        let contact = collide(&b, &a).expect("Shapes overlap");
+        assert_close(contact.normal, Vector3::new(-1.0, 0.0, 0.0), 0.001);
+    }
+
+    #[test]
+    fn boxes_separate() {
+        let cube = Collider::obb(Vector3::new(1.0, 1.0, 1.0));
+        let a = WorldShape::new(&cube, &at(Vector3::new(0.0, 0.0, 0.0)));
+
+        assert!(collide(&a, &WorldShape::new(&cube, &at(Vector3::new(2.5, 0.0, 0.0)))).is_none());
+        assert!(collide(&a, &WorldShape::new(&cube, &at(Vector3::new(2.1, 2.1, 0.0)))).is_none());
+
+        // Turned by 45 degrees, the box reaches out to the square root of 2:
+        let turned = WorldShape::new(&cube, &placed(Vector3::new(2.5, 0.0, 0.0), Vector3::new(0.0, FRAC_PI_4, 0.0)));
+        assert!(collide(&a, &turned).is_none());
+
+        let turned = WorldShape::new(&cube, &placed(Vector3::new(2.3, 0.0, 0.0), Vector3::new(0.0, FRAC_PI_4, 0.0)));
+        let contact = collide(&a, &turned).expect("Shapes overlap");
+        assert!((contact.depth - (1.0 + 2f32.sqrt() - 2.3)).abs() < 0.001, "Depth {}", contact.depth);
+    }
+
+    #[test]
+    fn deep_penetration() {
+        let cube = Collider::obb(Vector3::new(1.0, 1.0, 1.0));
+        let wide = Collider::obb(Vector3::new(4.0, 1.0, 4.0));
+        let a = WorldShape::new(&wide, &at(Vector3::new(0.0, 0.0, 0.0)));
+        let b = WorldShape::new(&cube, &at(Vector3::new(0.5, 0.4, -0.3)));
+
+        // Almost entirely inside, the shortest way out is up:
+        let contact = collide(&a, &b).expect("Shapes overlap");
+        assert!((contact.depth - 1.6).abs() < 0.001, "Depth {}", contact.depth);
+        assert_close(contact.normal, Vector3::new(0.0, 1.0, 0.0), 0.001);
+    }
+
+    #[test]
+    fn box_resting_on_ground_touches_at_its_corners() {
+        let (ground, cube) = (Collider::aabb(Vector3::new(10.0, 0.5, 10.0)), Collider::obb(Vector3::new(1.0, 1.0, 1.0)));
+        let a = WorldShape::new(&ground, &at(Vector3::new(0.0, -0.5, 0.0)));
+        let b = WorldShape::new(&cube, &placed(Vector3::new(0.3, 0.95, 0.0), Vector3::new(0.0, 0.4, 0.0)));
+
+        let contact = collide(&a, &b).expect("Shapes overlap");
+        let points = contact_points(&a, &b, &contact);
+
+        assert_eq!(points.len(), 4);
+        for point in points {
+            assert!((point.depth - 0.05).abs() < 0.001, "Depth {}", point.depth);
+            assert!((point.position.y + 0.025).abs() < 0.001, "Position {:?}", point.position);
+        }
+    }
+
+    #[test]
+    fn box_on_edge_touches_along_it() {
+        let (ground, cube) = (Collider::aabb(Vector3::new(10.0, 0.5, 10.0)), Collider::obb(Vector3::new(1.0, 1.0, 1.0)));
+        let a = WorldShape::new(&ground, &at(Vector3::new(0.0, -0.5, 0.0)));
+        let b = WorldShape::new(&cube, &placed(Vector3::new(0.0, 1.2, 0.0), Vector3::new(0.0, 0.0, 0.3)));
+
+        let contact = collide(&a, &b).expect("Shapes overlap");
+        assert_eq!(contact_points(&a, &b, &contact).len(), 2);
+    }
+
+    #[test]
+    fn turned_box_on_box_is_cut_down_to_four_points() {
+        let cube = Collider::obb(Vector3::new(1.0, 1.0, 1.0));
+        let a = WorldShape::new(&cube, &at(Vector3::new(0.0, 0.0, 0.0)));
+        let b = WorldShape::new(&cube, &placed(Vector3::new(0.0, 1.9, 0.0), Vector3::new(0.0, FRAC_PI_4, 0.0)));
+
+        let contact = collide(&a, &b).expect("Shapes overlap");
+        assert_eq!(contact_points(&a, &b, &contact).len(), 4);
+    }
+}
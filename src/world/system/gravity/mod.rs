@@ -0,0 +1,354 @@
+use crate::world::system::{System, SystemRuntimeError, Stage, SystemId, SystemAccess};
+use crate::world::system::integrate::IntegrateSystem;
+use crate::world::system::members::MemberList;
+use crate::world::entity::event::WorldEvent;
+use crate::world::resource::{Resources, ResourceManager};
+use crate::world::entity::component::ComponentManager;
+use crate::world::entity::component::transform::Transform;
+use crate::world::entity::EntityContainer;
+use std::time::Duration;
+use cgmath::Vector3;
+use crate::world::entity::component::rigid_body::RigidBody;
+use cgmath::InnerSpace;
+use rayon::prelude::*;
+use self::octree::{Octree, attraction};
+
+pub mod octree;
+
+pub const G: f64 = 0.000000000067f64;
+
+// The name gravity is committed under, so that integrators can tell it apart from other forces:
+pub const GRAVITY_FORCE: &str = "gravity";
+
+// World resource, so that the simulation can be tuned at runtime:
+pub struct Gravity {
+    pub constant: f64,
+    // Barnes-Hut opening angle: clusters that look smaller than this from a body pull on it as
+    // one. Larger is faster and less accurate, 0 is exact.
+    pub theta: f64,
+    // Keeps the pull between bodies that come very close, or coincide, finite:
+    pub softening: f64,
+    // Below this many bodies, building the tree costs more than comparing every pair:
+    pub exact_below: usize
+}
+
+impl Default for Gravity {
+    fn default() -> Self {
+        Gravity {
+            constant: G,
+            theta: 0.5,
+            softening: 0.01,
+            exact_below: 64
+        }
+    }
+}
+
+// World resource choosing what bodies are pulled by:
+#[derive(Copy, Clone, Debug)]
+pub enum GravityMode {
+    // Every body pulls on every other one:
+    NBody,
+    // The same pull everywhere, e.g. towards the ground of a level:
+    Uniform { direction: Vector3<f32>, magnitude: f32 },
+    // A pull towards a fixed point, falling off with the square of the distance. The strength is
+    // the acceleration at unit distance:
+    Radial { center: Vector3<f32>, strength: f32 }
+}
+
+impl Default for GravityMode {
+    fn default() -> Self {
+        GravityMode::NBody
+    }
+}
+
+impl Gravity {
+    // The acceleration each body feels, given the absolute positions and masses of all of them.
+    // Bodies that are None neither pull nor get pulled.
+    pub fn accelerations(&self, mode: &GravityMode, bodies: &[Option<(Vector3<f32>, f64)>]) -> Vec<Vector3<f32>> {
+        match *mode {
+            GravityMode::NBody => self.n_body_accelerations(bodies),
+            GravityMode::Uniform { direction, magnitude } => {
+                let acceleration = if direction.magnitude2() > 0.0 { direction.normalize() * magnitude } else { direction };
+
+                bodies
+                    .iter()
+                    .map(|body| if body.is_some() { acceleration } else { Vector3::new(0.0, 0.0, 0.0) })
+                    .collect()
+            },
+            GravityMode::Radial { center, strength } => {
+                let center = Vector3::new(center.x as f64, center.y as f64, center.z as f64);
+
+                bodies
+                    .iter()
+                    .map(|body| match *body {
+                        Some((pos, _)) => {
+                            let pos = Vector3::new(pos.x as f64, pos.y as f64, pos.z as f64);
+                            let pull = attraction(pos, center, strength as f64, self.softening);
+                            Vector3::new(pull.x as f32, pull.y as f32, pull.z as f32)
+                        },
+                        None => Vector3::new(0.0, 0.0, 0.0)
+                    })
+                    .collect()
+            }
+        }
+    }
+
+    fn n_body_accelerations(&self, bodies: &[Option<(Vector3<f32>, f64)>]) -> Vec<Vector3<f32>> {
+        let positions: Vec<Vector3<f64>> = bodies
+            .iter()
+            .map(|body| body.map_or(Vector3::new(0.0, 0.0, 0.0), |(pos, _)| Vector3::new(pos.x as f64, pos.y as f64, pos.z as f64)))
+            .collect();
+
+        let masses: Vec<f64> = bodies
+            .iter()
+            .map(|body| body.map_or(0.0, |(_, mass)| mass.max(0.0)))
+            .collect();
+
+        let fields = if masses.iter().filter(|&&mass| mass > 0.0).count() < self.exact_below {
+            self.exact_fields(&positions, &masses)
+        } else {
+            self.approximate_fields(&positions, &masses)
+        };
+
+        fields
+            .into_iter()
+            .zip(bodies)
+            .map(|(field, body)| match body {
+                Some(_) => Vector3::new(field.x as f32, field.y as f32, field.z as f32) * self.constant as f32,
+                None => Vector3::new(0.0, 0.0, 0.0)
+            })
+            .collect()
+    }
+
+    fn exact_fields(&self, positions: &[Vector3<f64>], masses: &[f64]) -> Vec<Vector3<f64>> {
+        let mut fields = vec![Vector3::new(0.0, 0.0, 0.0); positions.len()];
+
+        for i in 0..positions.len() {
+            for j in (i+1)..positions.len() {
+                // Per unit of mass, so that each side only has to be scaled by the other's mass:
+                let pull = attraction(positions[i], positions[j], 1.0, self.softening);
+
+                fields[i] += pull * masses[j];
+                fields[j] -= pull * masses[i];
+            }
+        }
+
+        fields
+    }
+
+    fn approximate_fields(&self, positions: &[Vector3<f64>], masses: &[f64]) -> Vec<Vector3<f64>> {
+        let octree = Octree::new(positions, masses);
+
+        (0..positions.len())
+            .into_par_iter()
+            .map(|i| octree.field_at(positions[i], Some(i), self.theta, self.softening))
+            .collect()
+    }
+}
+
+pub struct GravitySystem {
+    rigid_bodies: MemberList<&'static mut RigidBody>,
+    gravity: Option<ResourceManager<Gravity>>,
+    mode: Option<ResourceManager<GravityMode>>
+}
+
+impl<'a> System<'a> for GravitySystem {
+    type Environment = (&'a MemberList<&'static mut RigidBody>, &'a ResourceManager<Gravity>, Option<&'a ResourceManager<GravityMode>>);
+
+    fn new() -> Self{
+        Self {
+            rigid_bodies: MemberList::new(),
+            gravity: None,
+            mode: None
+        }
+    }
+
+    fn stage() -> Stage {
+        Stage::Simulate
+    }
+
+    // Forces have to be committed before they are integrated:
+    fn before() -> Vec<SystemId> {
+        vec![SystemId::of::<IntegrateSystem>()]
+    }
+
+    fn access() -> SystemAccess {
+        SystemAccess::new()
+            .write::<RigidBody>()
+            .read::<Transform>()
+            .read::<Gravity>()
+            .read::<GravityMode>()
+    }
+
+    fn on_fetch<T: EntityContainer>(&mut self, source: &T, resources: &Resources) -> Result<(), SystemRuntimeError>{
+        self.gravity = resources.fetch::<Gravity>();
+        self.mode = resources.fetch::<GravityMode>();
+        self.rigid_bodies.fetch(source);
+        Result::Ok(())
+    }
+
+    fn on_event(&mut self, event: &WorldEvent) -> bool {
+        self.rigid_bodies.apply(event)
+    }
+
+    // Without a GravityMode resource, bodies attract each other:
+    fn on_freeze(&'a self) -> Result<Self::Environment, SystemRuntimeError> {
+        match self.gravity {
+            Some(ref gravity) => Result::Ok((&self.rigid_bodies, gravity, self.mode.as_ref())),
+            None => Result::Err(SystemRuntimeError::new("Missing Gravity resource"))
+        }
+    }
+
+    fn on_run(&self, (rigid_bodies, gravity, mode): Self::Environment, _delta: Duration) {
+        let mode = mode.map(|mode| *mode.lock_resource_for_read()).unwrap_or_default();
+
+        // Snapshot the bodies first, so that no two of them are ever locked at the same time. The
+        // positions are the ones the integrator starts from, including last tick's corrections by
+        // the solver:
+        let (rigid_bodies, bodies): (Vec<&ComponentManager<RigidBody>>, Vec<Option<(Vector3<f32>, f64)>>) = rigid_bodies
+            .iter()
+            .map(|item| {
+                let position = item.entity.world_transform().position;
+                let body = item.managers().lock_component_for_read();
+                let source = if body.enabled && body.mass > 0f32 { Some((position, body.mass as f64)) } else { None };
+
+                (item.managers(), source)
+            })
+            .unzip();
+
+        let accelerations = gravity.lock_resource_for_read().accelerations(&mode, &bodies);
+
+        // Apply forces:
+        for (i, acceleration) in accelerations.into_iter().enumerate() {
+            if let Some((_, mass)) = bodies[i] {
+                let mut body = rigid_bodies[i].lock_component_for_write();
+                let gravity_scale = body.gravity_scale;
+                body.commit_force(GRAVITY_FORCE, acceleration * mass as f32 * gravity_scale);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::system::integrate::Integrator;
+    use crate::world::system::scheduler::Scheduler;
+    use crate::world::entity::builder::EntityBuilder;
+    use crate::world::entity::registry::EntityRegistry;
+    use rand::{Rng, SeedableRng};
+    use rand::rngs::StdRng;
+
+    fn cluster(n: usize) -> (Vec<Vector3<f64>>, Vec<f64>) {
+        let mut rng = StdRng::seed_from_u64(24);
+
+        let positions = (0..n)
+            .map(|_| Vector3::new(rng.gen_range(-100.0, 100.0), rng.gen_range(-100.0, 100.0), rng.gen_range(-100.0, 100.0)))
+            .collect();
+        let masses = (0..n).map(|_| rng.gen_range(1.0, 1000.0)).collect();
+
+        (positions, masses)
+    }
+
+    // The error relative to the size of the exact field, over all bodies at once, as a body with
+    // a weak net pull has a large relative error even when the approximation is good:
+    fn relative_error(approximate: &[Vector3<f64>], exact: &[Vector3<f64>]) -> f64 {
+        let error: f64 = approximate.iter().zip(exact).map(|(a, e)| (a - e).magnitude()).sum();
+        let size: f64 = exact.iter().map(|e| e.magnitude()).sum();
+
+        error / size
+    }
+
+    #[test]
+    fn opening_angle_of_zero_is_exact() {
+        let gravity = Gravity { theta: 0.0, ..Gravity::default() };
+        let (positions, masses) = cluster(500);
+
+        let exact = gravity.exact_fields(&positions, &masses);
+        let approximate = gravity.approximate_fields(&positions, &masses);
+
+        for (a, e) in approximate.iter().zip(exact.iter()) {
+            assert!((a - e).magnitude() <= e.magnitude() * 1e-9, "{:?} != {:?}", a, e);
+        }
+    }
+
+    #[test]
+    fn default_opening_angle_is_close() {
+        let gravity = Gravity::default();
+        let (positions, masses) = cluster(500);
+
+        let exact = gravity.exact_fields(&positions, &masses);
+        let approximate = gravity.approximate_fields(&positions, &masses);
+
+        let error = relative_error(&approximate, &exact);
+        assert!(error < 0.01, "Relative error {} with theta {}", error, gravity.theta);
+    }
+
+    // Lets bodies of the given masses and gravity scales fall for a few ticks, returning where
+    // they ended up and how fast they are:
+    fn fall(mode: GravityMode, integrator: Integrator, bodies: &[(f32, f32)]) -> Vec<(Vector3<f32>, Vector3<f32>)> {
+        let root = EntityBuilder::new()
+            .with_children(bodies
+                .iter()
+                .map(|&(mass, gravity_scale)| EntityBuilder::new()
+                    .with_component(Transform::new())
+                    .with_component(RigidBody::new(mass).with_gravity_scale(gravity_scale)))
+                .collect())
+            .build();
+        let registry = EntityRegistry::new();
+        registry.adopt(&root);
+
+        let resources = Resources::new();
+        resources.insert(Gravity::default());
+        resources.insert(mode);
+        resources.insert(integrator);
+
+        let mut scheduler = Scheduler::new(root.clone(), resources, Duration::from_millis(10));
+        scheduler.register::<GravitySystem>();
+        scheduler.register::<IntegrateSystem>();
+        scheduler.schedule().expect("Couldn't schedule systems!");
+        for _ in 0..10 {
+            scheduler.tick().expect("Couldn't run systems!");
+        }
+
+        root.query::<(&Transform, &RigidBody)>()
+            .map(|item| {
+                let (transform, rigid_body) = item.lock();
+                (transform.position, rigid_body.velocity)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn uniform_gravity_doesnt_depend_on_mass() {
+        let down = GravityMode::Uniform { direction: Vector3::new(0.0, -2.0, 0.0), magnitude: 9.81 };
+
+        for &integrator in [Integrator::SemiImplicitEuler, Integrator::VelocityVerlet, Integrator::Rk4].iter() {
+            let states = fall(down, integrator, &[(1.0, 1.0), (1000.0, 1.0), (0.001, 1.0)]);
+
+            // Ten ticks of 10 ms:
+            assert!((states[0].1.y + 0.981).abs() < 0.0001, "{:?}: {:?}", integrator, states[0]);
+            for state in &states[1..] {
+                assert!((state.0 - states[0].0).magnitude() < 0.00001, "{:?}: {:?}", integrator, states);
+                assert!((state.1 - states[0].1).magnitude() < 0.00001, "{:?}: {:?}", integrator, states);
+            }
+        }
+    }
+
+    #[test]
+    fn zero_gravity_scale_floats() {
+        let modes = [
+            GravityMode::Uniform { direction: Vector3::new(0.0, -1.0, 0.0), magnitude: 9.81 },
+            GravityMode::Radial { center: Vector3::new(10.0, 0.0, 0.0), strength: 100.0 }
+        ];
+
+        for &mode in modes.iter() {
+            for &integrator in [Integrator::SemiImplicitEuler, Integrator::VelocityVerlet, Integrator::Rk4].iter() {
+                let states = fall(mode, integrator, &[(1.0, 0.0), (1.0, 1.0)]);
+
+                assert_eq!(states[0], (Vector3::new(0.0, 0.0, 0.0), Vector3::new(0.0, 0.0, 0.0)), "{:?} {:?}", mode, integrator);
+                assert!(states[1].1.magnitude() > 0.1, "{:?} {:?}", mode, integrator);
+            }
+        }
+    }
+}
@@ -0,0 +1,188 @@
+use cgmath::{Vector3, InnerSpace};
+
+// Levels below the root, after which a leaf keeps all bodies it gets instead of splitting, so that
+// bodies at the same position don't split it forever:
+const MAX_DEPTH: usize = 32;
+
+struct Node {
+    center: Vector3<f64>,
+    half_size: f64,
+    mass: f64,
+    center_of_mass: Vector3<f64>,
+    // Index of the first of eight consecutive children, if the node has been split:
+    children: Option<usize>,
+    bodies: Vec<usize>
+}
+
+impl Node {
+    fn new(center: Vector3<f64>, half_size: f64) -> Self {
+        Node {
+            center,
+            half_size,
+            mass: 0.0,
+            center_of_mass: Vector3::new(0.0, 0.0, 0.0),
+            children: None,
+            bodies: Vec::new()
+        }
+    }
+
+    fn octant_of(&self, position: Vector3<f64>) -> usize {
+        (if position.x >= self.center.x { 1 } else { 0 }) |
+            (if position.y >= self.center.y { 2 } else { 0 }) |
+            (if position.z >= self.center.z { 4 } else { 0 })
+    }
+
+    fn contains(&self, position: Vector3<f64>) -> bool {
+        let offset = position - self.center;
+        offset.x.abs() <= self.half_size && offset.y.abs() <= self.half_size && offset.z.abs() <= self.half_size
+    }
+}
+
+// Groups bodies into cubes that halve in size at every level, so that a distant cluster of
+// bodies can pull as one body at its center of mass.
+pub struct Octree<'a> {
+    positions: &'a [Vector3<f64>],
+    masses: &'a [f64],
+    nodes: Vec<Node>
+}
+
+impl<'a> Octree<'a> {
+    // Only bodies with a positive mass are put in the tree:
+    pub fn new(positions: &'a [Vector3<f64>], masses: &'a [f64]) -> Self {
+        let bodies: Vec<usize> = (0..positions.len()).filter(|&i| masses[i] > 0.0).collect();
+
+        let (min, max) = bodies.iter().fold(
+            (Vector3::new(f64::MAX, f64::MAX, f64::MAX), Vector3::new(f64::MIN, f64::MIN, f64::MIN)),
+            |(min, max), &i| {
+                let p = positions[i];
+                (Vector3::new(min.x.min(p.x), min.y.min(p.y), min.z.min(p.z)),
+                 Vector3::new(max.x.max(p.x), max.y.max(p.y), max.z.max(p.z)))
+            }
+        );
+
+        let root = if bodies.is_empty() {
+            Node::new(Vector3::new(0.0, 0.0, 0.0), 1.0)
+        } else {
+            let extent = max - min;
+            Node::new((min + max) * 0.5, (extent.x.max(extent.y).max(extent.z) * 0.5).max(f64::EPSILON))
+        };
+
+        let mut octree = Octree { positions, masses, nodes: vec![root] };
+
+        for body in bodies {
+            octree.insert(0, body, 0);
+        }
+
+        octree.summarize(0);
+        octree
+    }
+
+    fn insert(&mut self, node: usize, body: usize, depth: usize) {
+        if let Some(first_child) = self.nodes[node].children {
+            let octant = self.nodes[node].octant_of(self.positions[body]);
+            self.insert(first_child + octant, body, depth + 1);
+            return;
+        }
+
+        self.nodes[node].bodies.push(body);
+
+        if self.nodes[node].bodies.len() > 1 && depth < MAX_DEPTH {
+            self.split(node);
+
+            for body in std::mem::replace(&mut self.nodes[node].bodies, Vec::new()) {
+                self.insert(node, body, depth);
+            }
+        }
+    }
+
+    fn split(&mut self, node: usize) {
+        let (center, half_size) = (self.nodes[node].center, self.nodes[node].half_size * 0.5);
+        let first_child = self.nodes.len();
+
+        for octant in 0..8 {
+            let offset = Vector3::new(
+                if octant & 1 != 0 { half_size } else { -half_size },
+                if octant & 2 != 0 { half_size } else { -half_size },
+                if octant & 4 != 0 { half_size } else { -half_size }
+            );
+
+            self.nodes.push(Node::new(center + offset, half_size));
+        }
+
+        self.nodes[node].children = Some(first_child);
+    }
+
+    // Sums up the mass and center of mass of every node from its leaves:
+    fn summarize(&mut self, node: usize) {
+        let mut mass = 0.0;
+        let mut weighted_position = Vector3::new(0.0, 0.0, 0.0);
+
+        if let Some(first_child) = self.nodes[node].children {
+            for child in first_child..first_child + 8 {
+                self.summarize(child);
+                mass += self.nodes[child].mass;
+                weighted_position += self.nodes[child].center_of_mass * self.nodes[child].mass;
+            }
+        }
+        else {
+            for &body in self.nodes[node].bodies.iter() {
+                mass += self.masses[body];
+                weighted_position += self.positions[body] * self.masses[body];
+            }
+        }
+
+        self.nodes[node].mass = mass;
+        if mass > 0.0 {
+            self.nodes[node].center_of_mass = weighted_position / mass;
+        }
+    }
+
+    // The field at a position, divided by the gravitational constant. Nodes that look smaller
+    // than the opening angle from the position, and don't contain it, count as a single body;
+    // a theta of 0 considers every body on its own. `skip` is left out, so that a body doesn't
+    // pull on itself.
+    pub fn field_at(&self, position: Vector3<f64>, skip: Option<usize>, theta: f64, softening: f64) -> Vector3<f64> {
+        let mut field = Vector3::new(0.0, 0.0, 0.0);
+        let mut stack = vec![0];
+
+        while let Some(node) = stack.pop() {
+            let node = &self.nodes[node];
+            if node.mass <= 0.0 {
+                continue;
+            }
+
+            match node.children {
+                Some(first_child) => {
+                    let distance = (node.center_of_mass - position).magnitude();
+
+                    if !node.contains(position) && node.half_size * 2.0 < theta * distance {
+                        field += attraction(position, node.center_of_mass, node.mass, softening);
+                    }
+                    else {
+                        stack.extend(first_child..first_child + 8);
+                    }
+                },
+                None => {
+                    for &body in node.bodies.iter().filter(|&&body| Some(body) != skip) {
+                        field += attraction(position, self.positions[body], self.masses[body], softening);
+                    }
+                }
+            }
+        }
+
+        field
+    }
+}
+
+// The pull of a mass on a position, divided by the gravitational constant. The softening length
+// keeps it finite as the two come close, and it vanishes where they coincide.
+pub fn attraction(position: Vector3<f64>, source: Vector3<f64>, mass: f64, softening: f64) -> Vector3<f64> {
+    let offset = source - position;
+    let distance_squared = offset.magnitude2() + softening * softening;
+
+    if distance_squared == 0.0 {
+        return Vector3::new(0.0, 0.0, 0.0);
+    }
+
+    offset * (mass / (distance_squared * distance_squared.sqrt()))
+}
@@ -1,4 +1,5 @@
-use crate::world::system::{System, SysEnvComponentMut, SystemRuntimeError, SysEnvComponent};
+use crate::world::system::{System, SysEnvComponentMut, SystemRuntimeError, SysEnvComponent, Stage, SystemAccess};
+use crate::world::resource::Resources;
 use crate::world::entity::component::{Component, ComponentManager, ComponentWriteAccess};
 use crate::world::entity::component::transform::Transform;
 use crate::world::entity::component::model::GraphicsModel;
@@ -11,32 +12,41 @@ use cgmath::Vector3;
 use futures::StreamExt;
 use crate::world::entity::component::rigid_body::RigidBody;
 use crate::world::entity::component::controller::Controller;
+use crate::world::entity::query::QueryItem;
+use crate::world::entity::event::WorldEvent;
+use crate::world::system::members::MemberList;
 
 pub struct InputSystem {
-    resources: Vec<(ComponentManager<Controller>, Entity)>
+    resources: MemberList<&'static mut Controller>
 }
 
 impl<'a> System<'a> for InputSystem {
-    type Environment = &'a Vec<(ComponentManager<Controller>, Entity)>;
+    type Environment = &'a MemberList<&'static mut Controller>;
 
     fn new() -> Self{
-        Self { resources: Vec::new() }
+        Self { resources: MemberList::new() }
     }
 
-    fn on_fetch<T: EntityContainer>(&mut self, source: &T) -> Result<(), SystemRuntimeError>{
-        self.resources.clear();
-
-        let mut resources = source.query_entities(true)
-            .map(|entity| (entity.component::<Controller>(), entity))
-            .filter(|(a, _)| a.is_some())
-            .map(|(a, b)| (a.unwrap(), b))
-            .collect();
+    fn stage() -> Stage {
+        Stage::Input
+    }
 
-        self.resources.append(&mut resources);
+    // Controllers commit their input as forces on the rigid body of the same entity:
+    fn access() -> SystemAccess {
+        SystemAccess::new()
+            .write::<Controller>()
+            .write::<RigidBody>()
+    }
 
+    fn on_fetch<T: EntityContainer>(&mut self, source: &T, _resources: &Resources) -> Result<(), SystemRuntimeError>{
+        self.resources.fetch(source);
         Result::Ok(())
     }
 
+    fn on_event(&mut self, event: &WorldEvent) -> bool {
+        self.resources.apply(event)
+    }
+
     fn on_freeze(&'a self) -> Result<Self::Environment, SystemRuntimeError> {
         Result::Ok(
             &self.resources
@@ -44,9 +54,12 @@ impl<'a> System<'a> for InputSystem {
     }
 
     fn on_run(&self, environment: Self::Environment, delta: Duration) {
-        for (ref controller, ref entity) in environment {
-            let controller: &mut Controller = &mut *controller.lock_component_for_write();
-            controller.update(entity, delta);            
+        for item in environment {
+            let controller: &mut Controller = &mut *item.lock();
+
+            if controller.enabled {
+                controller.update(&item.entity, delta);
+            }
         }
     }
 }
@@ -1,72 +1,318 @@
-use crate::world::system::{System, SysEnvComponentMut, SystemRuntimeError, SysEnvComponent};
-use crate::world::entity::component::{Component, ComponentManager, ComponentWriteAccess};
+use crate::world::system::{System, SystemRuntimeError, Stage, SystemAccess};
+use crate::world::resource::Resources;
 use crate::world::entity::component::transform::Transform;
-use crate::world::entity::component::model::GraphicsModel;
-use crate::world::entity::{Entity, EntityContainer, EntityIterator};
-use std::sync::Arc;
-use crate::world::World;
-use std::ops::DerefMut;
+use crate::world::entity::EntityContainer;
 use std::time::Duration;
 use cgmath::Vector3;
-use futures::StreamExt;
 use crate::world::entity::component::rigid_body::RigidBody;
+use crate::world::entity::event::WorldEvent;
+use crate::world::system::members::MemberList;
+use crate::world::system::gravity::{Gravity, GravityMode, GRAVITY_FORCE};
+use crate::world::resource::ResourceManager;
+
+type IntegrateQuery = (&'static mut Transform, &'static mut RigidBody);
+
+// World resource choosing how motion is integrated over a tick:
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Integrator {
+    // Velocity first, then position from the new velocity. Cheap and keeps orbits bounded:
+    SemiImplicitEuler,
+    // Second order; evaluates gravity once more at the new positions:
+    VelocityVerlet,
+    // Fourth order; evaluates gravity three more times per tick:
+    Rk4
+}
+
+impl Default for Integrator {
+    fn default() -> Self {
+        Integrator::SemiImplicitEuler
+    }
+}
+
+// The state of a body as the integrators see it. Gravity depends on where all the bodies are,
+// so it is evaluated again at intermediate positions, while other forces are held constant
+// over the tick.
+struct Body {
+    movable: bool,
+    mass: f32,
+    position: Vector3<f32>,
+    velocity: Vector3<f32>,
+    acceleration: Vector3<f32>,
+    // The part of the acceleration that doesn't come from gravity:
+    constant_acceleration: Vector3<f32>,
+    gravitating: bool,
+    gravity_scale: f32
+}
 
 pub struct IntegrateSystem {
-    components: Vec<(ComponentManager<Transform>, ComponentManager<RigidBody>)>
+    components: MemberList<IntegrateQuery>,
+    integrator: Option<ResourceManager<Integrator>>,
+    gravity: Option<ResourceManager<Gravity>>,
+    gravity_mode: Option<ResourceManager<GravityMode>>
 }
 
 impl<'a> System<'a> for IntegrateSystem {
-    type Environment = &'a Vec<(ComponentManager<Transform>, ComponentManager<RigidBody>)>;
+    type Environment = (
+        &'a MemberList<IntegrateQuery>,
+        Option<&'a ResourceManager<Integrator>>,
+        Option<&'a ResourceManager<Gravity>>,
+        Option<&'a ResourceManager<GravityMode>>
+    );
 
     fn new() -> Self{
-        Self { components: Vec::new() }
+        Self {
+            components: MemberList::new(),
+            integrator: None,
+            gravity: None,
+            gravity_mode: None
+        }
     }
 
-    fn on_fetch<T: EntityContainer>(&mut self, source: &T) -> Result<(), SystemRuntimeError>{
-        self.components.clear();
-
-        let mut new_components = source.query_entities(true)
-            .map(|entity| (entity.component::<Transform>(), entity.component::<RigidBody>()))
-            .filter(|(a, b)| a.is_some() && b.is_some())
-            .map(|(a, b)| (a.unwrap(), b.unwrap()))
-            .collect();
+    fn stage() -> Stage {
+        Stage::Simulate
+    }
 
-        self.components.append(&mut new_components);
+    fn access() -> SystemAccess {
+        SystemAccess::new()
+            .write::<Transform>()
+            .write::<RigidBody>()
+            .read::<Integrator>()
+            .read::<Gravity>()
+            .read::<GravityMode>()
+    }
 
+    fn on_fetch<T: EntityContainer>(&mut self, source: &T, resources: &Resources) -> Result<(), SystemRuntimeError>{
+        self.integrator = resources.fetch::<Integrator>();
+        self.gravity = resources.fetch::<Gravity>();
+        self.gravity_mode = resources.fetch::<GravityMode>();
+        self.components.fetch(source);
         Result::Ok(())
     }
 
+    fn on_event(&mut self, event: &WorldEvent) -> bool {
+        self.components.apply(event)
+    }
+
+    // The resources are optional; without them, the default integrator and gravity mode are used,
+    // or without Gravity, gravity is held constant over the tick like any other force:
     fn on_freeze(&'a self) -> Result<Self::Environment, SystemRuntimeError> {
-        Result::Ok(
-            &self.components
-        )
+        Result::Ok((&self.components, self.integrator.as_ref(), self.gravity.as_ref(), self.gravity_mode.as_ref()))
     }
 
-    fn on_run(&self, environment: Self::Environment, delta: Duration) {
+    fn on_run(&self, (components, integrator, gravity, gravity_mode): Self::Environment, delta: Duration) {
         let delta = delta.as_secs_f32();
+        let integrator = integrator.map(|integrator| *integrator.lock_resource_for_read()).unwrap_or_default();
+        let gravity = gravity.map(|gravity| gravity.lock_resource_for_read());
+        let gravity_mode = gravity_mode.map(|gravity_mode| *gravity_mode.lock_resource_for_read()).unwrap_or_default();
+        let gravity = gravity.as_deref().map(|gravity| (gravity, &gravity_mode));
 
-        for (transform, rigid_body) in environment {
-            let transform: &mut Transform = &mut *transform.lock_component_for_write();
-            let rigid_body: &mut RigidBody = &mut *rigid_body.lock_component_for_write();
-            
-            if !rigid_body.movable { continue; }
+        let bodies: Vec<Body> = components
+            .iter()
+            .map(|item| {
+                // The same positions GravitySystem computed the committed gravity at, so that the
+                // gravity the integrators evaluate later on matches it:
+                let position = item.entity.world_transform().position;
+                let (transform, rigid_body) = item.lock();
+                let net_force = rigid_body.net_force();
+                let gravity_force = rigid_body.force(GRAVITY_FORCE);
+                let inverse_mass = rigid_body.inverse_mass();
 
-            let Transform { ref mut position, ref mut angular_rotation, ..} = *transform;
+                Body {
+                    movable: rigid_body.movable && rigid_body.enabled && transform.enabled,
+                    mass: if rigid_body.enabled { rigid_body.mass } else { 0.0 },
+                    position,
+                    velocity: rigid_body.velocity,
+                    acceleration: net_force * inverse_mass,
+                    constant_acceleration: (net_force - gravity_force.unwrap_or_else(|| Vector3::new(0.0, 0.0, 0.0))) * inverse_mass,
+                    gravitating: gravity_force.is_some(),
+                    gravity_scale: rigid_body.gravity_scale
+                }
+            })
+            .collect();
+
+        let states = match integrator {
+            Integrator::SemiImplicitEuler => semi_implicit_euler(&bodies, delta),
+            Integrator::VelocityVerlet => velocity_verlet(&bodies, gravity, delta),
+            Integrator::Rk4 => rk4(&bodies, gravity, delta)
+        };
 
-            let net_force = rigid_body.net_force();
+        for ((item, body), (position, velocity)) in components.iter().zip(bodies.iter()).zip(states) {
+            if !body.movable { continue; }
 
-            let RigidBody { ref mut velocity, 
-                            ref mut acceleration, 
-                            ref mut angular_velocity, 
-                            ref mut angular_acceleration, mass, ..} = *rigid_body;
+            let (mut transform, mut rigid_body) = item.lock();
+            let transform: &mut Transform = &mut *transform;
+            let rigid_body: &mut RigidBody = &mut *rigid_body;
 
-            *position += *velocity * delta;
-            *velocity += *acceleration * delta;
-            *acceleration = net_force / mass;
+            // Positions add up along the hierarchy, so the local position moves as much:
+            transform.position += position - body.position;
+            rigid_body.velocity = velocity;
+            rigid_body.acceleration = body.acceleration;
 
             // TODO: Perhaps incorrect:
-            *angular_rotation += *angular_velocity * delta;
-            *angular_velocity += *angular_acceleration * delta;
+            rigid_body.angular_velocity += rigid_body.angular_acceleration * delta;
+            transform.angular_rotation += rigid_body.angular_velocity * delta;
         }
     }
 }
+
+// The accelerations of all bodies, were they at the given positions:
+fn accelerations_at(bodies: &[Body], positions: &[Vector3<f32>], gravity: Option<(&Gravity, &GravityMode)>) -> Vec<Vector3<f32>> {
+    let (gravity, gravity_mode) = match gravity {
+        Some(gravity) => gravity,
+        None => return bodies.iter().map(|body| body.acceleration).collect()
+    };
+
+    let sources: Vec<Option<(Vector3<f32>, f64)>> = bodies
+        .iter()
+        .zip(positions)
+        .map(|(body, position)| if body.mass > 0.0 { Some((*position, body.mass as f64)) } else { None })
+        .collect();
+
+    gravity
+        .accelerations(gravity_mode, &sources)
+        .into_iter()
+        .zip(bodies)
+        .map(|(field, body)| if body.gravitating { body.constant_acceleration + field * body.gravity_scale } else { body.constant_acceleration })
+        .collect()
+}
+
+// Bodies that can't move stay where they are, but still pull on the others:
+fn advance(bodies: &[Body], derivatives: &[Vector3<f32>], dt: f32) -> Vec<Vector3<f32>> {
+    bodies
+        .iter()
+        .zip(derivatives)
+        .map(|(body, derivative)| if body.movable { body.position + derivative * dt } else { body.position })
+        .collect()
+}
+
+fn semi_implicit_euler(bodies: &[Body], dt: f32) -> Vec<(Vector3<f32>, Vector3<f32>)> {
+    bodies
+        .iter()
+        .map(|body| {
+            let velocity = body.velocity + body.acceleration * dt;
+            (body.position + velocity * dt, velocity)
+        })
+        .collect()
+}
+
+fn velocity_verlet(bodies: &[Body], gravity: Option<(&Gravity, &GravityMode)>, dt: f32) -> Vec<(Vector3<f32>, Vector3<f32>)> {
+    let steps: Vec<Vector3<f32>> = bodies
+        .iter()
+        .map(|body| body.velocity + body.acceleration * (0.5 * dt))
+        .collect();
+
+    let positions = advance(bodies, &steps, dt);
+    let accelerations = accelerations_at(bodies, &positions, gravity);
+
+    bodies
+        .iter()
+        .zip(positions)
+        .zip(accelerations)
+        .map(|((body, position), acceleration)| (position, body.velocity + (body.acceleration + acceleration) * (0.5 * dt)))
+        .collect()
+}
+
+fn rk4(bodies: &[Body], gravity: Option<(&Gravity, &GravityMode)>, dt: f32) -> Vec<(Vector3<f32>, Vector3<f32>)> {
+    let k1_x: Vec<Vector3<f32>> = bodies.iter().map(|body| body.velocity).collect();
+    let k1_v: Vec<Vector3<f32>> = bodies.iter().map(|body| body.acceleration).collect();
+
+    let k2_x: Vec<Vector3<f32>> = bodies.iter().zip(&k1_v).map(|(body, k)| body.velocity + k * (0.5 * dt)).collect();
+    let k2_v = accelerations_at(bodies, &advance(bodies, &k1_x, 0.5 * dt), gravity);
+
+    let k3_x: Vec<Vector3<f32>> = bodies.iter().zip(&k2_v).map(|(body, k)| body.velocity + k * (0.5 * dt)).collect();
+    let k3_v = accelerations_at(bodies, &advance(bodies, &k2_x, 0.5 * dt), gravity);
+
+    let k4_x: Vec<Vector3<f32>> = bodies.iter().zip(&k3_v).map(|(body, k)| body.velocity + k * dt).collect();
+    let k4_v = accelerations_at(bodies, &advance(bodies, &k3_x, dt), gravity);
+
+    (0..bodies.len())
+        .map(|i| {
+            let dx = (k1_x[i] + k2_x[i] * 2.0 + k3_x[i] * 2.0 + k4_x[i]) * (dt / 6.0);
+            let dv = (k1_v[i] + k2_v[i] * 2.0 + k3_v[i] * 2.0 + k4_v[i]) * (dt / 6.0);
+            (bodies[i].position + dx, bodies[i].velocity + dv)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cgmath::InnerSpace;
+
+    const DT: f32 = 0.01;
+    // About two orbits:
+    const TICKS: usize = 1000;
+
+    // Two equal masses a unit apart, circling their common center of mass:
+    fn orbit() -> Vec<Body> {
+        let speed = (0.5f32).sqrt();
+
+        [(-0.5, -speed), (0.5, speed)]
+            .iter()
+            .map(|&(x, v)| Body {
+                movable: true,
+                mass: 1.0,
+                position: Vector3::new(x, 0.0, 0.0),
+                velocity: Vector3::new(0.0, 0.0, v),
+                acceleration: Vector3::new(0.0, 0.0, 0.0),
+                constant_acceleration: Vector3::new(0.0, 0.0, 0.0),
+                gravitating: true,
+                gravity_scale: 1.0
+            })
+            .collect()
+    }
+
+    fn energy(bodies: &[Body], gravity: &Gravity) -> f64 {
+        let kinetic: f64 = bodies.iter().map(|body| 0.5 * body.mass as f64 * body.velocity.magnitude2() as f64).sum();
+        let distance = (bodies[1].position - bodies[0].position).magnitude() as f64;
+        let potential = -gravity.constant * bodies[0].mass as f64 * bodies[1].mass as f64 / distance;
+
+        kinetic + potential
+    }
+
+    // The largest relative deviation from the initial energy over all ticks. Gravity is committed
+    // at the start of every tick like GravitySystem does:
+    fn drift<F>(integrate: F) -> f64 where F: Fn(&[Body], Option<(&Gravity, &GravityMode)>) -> Vec<(Vector3<f32>, Vector3<f32>)> {
+        let gravity = Gravity { constant: 1.0, softening: 0.0, ..Gravity::default() };
+        let mode = GravityMode::NBody;
+        let mut bodies = orbit();
+        let initial = energy(&bodies, &gravity);
+        let mut worst: f64 = 0.0;
+
+        for _ in 0..TICKS {
+            let positions: Vec<Vector3<f32>> = bodies.iter().map(|body| body.position).collect();
+            let accelerations = accelerations_at(&bodies, &positions, Some((&gravity, &mode)));
+            for (body, acceleration) in bodies.iter_mut().zip(accelerations) {
+                body.acceleration = acceleration;
+            }
+
+            let states = integrate(&bodies, Some((&gravity, &mode)));
+            for (body, (position, velocity)) in bodies.iter_mut().zip(states) {
+                body.position = position;
+                body.velocity = velocity;
+            }
+
+            worst = worst.max(((energy(&bodies, &gravity) - initial) / initial).abs());
+        }
+
+        worst
+    }
+
+    #[test]
+    fn semi_implicit_euler_conserves_energy() {
+        let drift = drift(|bodies, _| semi_implicit_euler(bodies, DT));
+        assert!(drift < 1e-3, "Energy drifted by {}", drift);
+    }
+
+    #[test]
+    fn velocity_verlet_conserves_energy() {
+        let drift = drift(|bodies, gravity| velocity_verlet(bodies, gravity, DT));
+        assert!(drift < 1e-5, "Energy drifted by {}", drift);
+    }
+
+    #[test]
+    fn rk4_conserves_energy() {
+        let drift = drift(|bodies, gravity| rk4(bodies, gravity, DT));
+        assert!(drift < 1e-5, "Energy drifted by {}", drift);
+    }
+}
@@ -0,0 +1,123 @@
+use std::collections::BTreeMap;
+use std::collections::btree_map::Values;
+use crate::world::entity::{Entity, EntityContainer};
+use crate::world::entity::event::WorldEvent;
+use crate::world::entity::query::{QueryItem, WorldQuery};
+use crate::world::entity::registry::EntityId;
+
+// The entities beneath a root that match a query, kept current through world events rather
+// than by querying the whole world again. Members are ordered by id, so iteration is stable.
+pub struct MemberList<Q: WorldQuery> {
+    root: Option<Entity>,
+    members: BTreeMap<EntityId, QueryItem<Q>>
+}
+
+impl<Q: WorldQuery> MemberList<Q> {
+    pub fn new() -> Self {
+        Self {
+            root: None,
+            members: BTreeMap::new()
+        }
+    }
+
+    pub fn fetch<T: EntityContainer>(&mut self, source: &T) {
+        self.root = Some(source.clone().into());
+        self.members = source
+            .query::<Q>()
+            .map(|item| (item.entity.id(), item))
+            .collect();
+    }
+
+    // Returns false if the list hasn't been fetched yet and can't be patched:
+    pub fn apply(&mut self, event: &WorldEvent) -> bool {
+        if self.root.is_none() {
+            return false;
+        }
+
+        match *event {
+            WorldEvent::EntityDespawned(id) => {
+                self.members.remove(&id);
+            },
+            // Enabling or disabling an entity shows or hides its whole subtree:
+            WorldEvent::EntityEnabled(id, _) => {
+                if let Some(entity) = self.lookup(id) {
+                    for descendant in entity.query_all_entities(true) {
+                        self.refresh(descendant.id());
+                    }
+                }
+            },
+            _ => self.refresh(event.entity())
+        }
+
+        true
+    }
+
+    fn refresh(&mut self, id: EntityId) {
+        self.members.remove(&id);
+
+        let item = self.lookup(id)
+            .filter(|entity| self.is_in_scope(entity))
+            .and_then(|entity| QueryItem::of(&entity));
+
+        if let Some(item) = item {
+            self.members.insert(id, item);
+        }
+    }
+
+    // Events only carry ids, which are resolved in the world the root belongs to:
+    fn lookup(&self, id: EntityId) -> Option<Entity> {
+        self.root
+            .as_ref()
+            .and_then(|root| root.registry())
+            .and_then(|registry| registry.lookup(id))
+    }
+
+    // Mirrors what a query from the root would see: the entity has to be beneath the root, with
+    // itself and everything in between enabled.
+    fn is_in_scope(&self, entity: &Entity) -> bool {
+        let root = match self.root {
+            Some(ref root) => root,
+            None => return false
+        };
+
+        let mut current = Some(entity.clone());
+        while let Some(entity) = current {
+            if !entity.is_enabled() {
+                return false;
+            }
+
+            if entity == *root {
+                return true;
+            }
+
+            current = entity.parent();
+        }
+
+        false
+    }
+
+    pub fn get(&self, id: EntityId) -> Option<&QueryItem<Q>> {
+        self.members.get(&id)
+    }
+
+    pub fn iter(&self) -> Values<EntityId, QueryItem<Q>> {
+        self.members.values()
+    }
+
+    pub fn len(&self) -> usize {
+        self.members.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.members.is_empty()
+    }
+}
+
+impl<'a, Q: WorldQuery> IntoIterator for &'a MemberList<Q> {
+    type Item = &'a QueryItem<Q>;
+    type IntoIter = Values<'a, EntityId, QueryItem<Q>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.members.values()
+    }
+}
@@ -13,77 +13,233 @@ use failure::_core::time::Duration;
 use std::time::Instant;
 use crate::world::entity::builder::EntityBuilder;
 use crate::world::entity::component::transform::Transform;
+use crate::world::entity::event::WorldEvent;
+use crate::world::resource::Resources;
 
 pub mod translate;
 pub mod integrate;
 pub mod gravity;
 pub mod input;
+pub mod scheduler;
+pub mod members;
+pub mod snapshot;
+pub mod collision;
+pub mod solver;
 
 type SysEnvComponent<'a, C> = ComponentReadAccess<'a, C>;
 type SysEnvComponentMut<'a, C> = ComponentWriteAccess<'a, C>;
 
+// Stages run in this order on every tick:
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub enum Stage {
+    Input,
+    Simulate,
+    PostSimulate,
+    RenderPrep
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub struct SystemId {
+    type_id: TypeId,
+    name: &'static str
+}
+
+impl SystemId {
+    pub fn of<S: 'static>() -> Self {
+        SystemId { type_id: TypeId::of::<S>(), name: type_name::<S>() }
+    }
+
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+}
+
+// The component and resource types a system touches while running. Only systems whose access
+// doesn't conflict run at the same time, so within a tick no system waits on a lock another
+// system holds, and systems can't deadlock by locking in opposite orders. It doesn't do away
+// with the locks: components and resources can also be reached from outside the scheduler,
+// e.g. by the renderer through World::query, so every use still goes through its lock. All of
+// this only holds as far as the access is declared truthfully.
+#[derive(Clone, Debug, Default)]
+pub struct SystemAccess {
+    reads: Vec<TypeId>,
+    writes: Vec<TypeId>,
+    exclusive: bool
+}
+
+impl SystemAccess {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // For systems that can't tell what they touch; they never run alongside another system:
+    pub fn exclusive() -> Self {
+        SystemAccess { exclusive: true, ..Self::default() }
+    }
+
+    pub fn read<T: 'static>(mut self) -> Self {
+        self.reads.push(TypeId::of::<T>());
+        self
+    }
+
+    pub fn write<T: 'static>(mut self) -> Self {
+        self.writes.push(TypeId::of::<T>());
+        self
+    }
+
+    pub fn conflicts_with(&self, other: &SystemAccess) -> bool {
+        self.exclusive || other.exclusive ||
+            self.writes.iter().any(|t| other.writes.contains(t) || other.reads.contains(t)) ||
+            other.writes.iter().any(|t| self.reads.contains(t))
+    }
+}
+
 pub trait System<'a>: Send + Sync
 {
     type Environment;
 
     fn new() -> Self;
 
-    fn on_fetch<T: EntityContainer>(&mut self, source: &T) -> Result<(), SystemRuntimeError>;
+    fn stage() -> Stage where Self: Sized {
+        Stage::Simulate
+    }
+
+    // Ordering constraints within the stage; systems that aren't registered are ignored:
+    fn before() -> Vec<SystemId> where Self: Sized {
+        Vec::new()
+    }
+
+    fn after() -> Vec<SystemId> where Self: Sized {
+        Vec::new()
+    }
+
+    fn access() -> SystemAccess where Self: Sized {
+        SystemAccess::exclusive()
+    }
+
+    fn retry_policy() -> RetryPolicy where Self: Sized {
+        RetryPolicy::default()
+    }
+
+    // Called before the first run after the system has been (re)started:
+    fn on_start(&mut self, _resources: &Resources) {}
+
+    // Called when the system is stopped or the world shuts down:
+    fn on_stop(&mut self, _resources: &Resources) {}
+
+    fn on_fetch<T: EntityContainer>(&mut self, source: &T, resources: &Resources) -> Result<(), SystemRuntimeError>;
+
+    // Lets a system patch its members when the world changes. Returning false makes the
+    // scheduler fall back to on_fetch before the next run.
+    fn on_event(&mut self, _event: &WorldEvent) -> bool {
+        false
+    }
 
     fn on_freeze(&'a self) -> Result<Self::Environment, SystemRuntimeError>;
 
     //fn on_recalculate(&'a mut self);
 
     fn on_run(&self, environment: Self::Environment, delta: Duration);
+}
 
-    fn start<'b, T: EntityContainer>(&'b mut self, source: T) where 'b: 'a {
-        let system_name = type_name::<Self>();
-
-        println!("{}: Initializing system...", system_name);
+pub struct SystemRuntimeError {
+    system: Option<&'static str>,
+    cause: String,
+    source: Option<Box<dyn Error + Send + Sync>>
+}
 
-        // Initialize:
-        while let Err(SystemRuntimeError(e)) = self.on_fetch(&source){
-            let timeout_s = 5;
-            print!("{}: System runtime error ({})", system_name, e);
-            println!("; Retrying in {} secs", timeout_s);
-            sleep(Duration::new(timeout_s, 0));
+impl SystemRuntimeError {
+    pub fn new<S: Into<String>>(cause: S) -> Self {
+        SystemRuntimeError {
+            system: None,
+            cause: cause.into(),
+            source: None
         }
+    }
 
-        println!("{}: Now online!", system_name);
+    pub fn with_source<E: Error + Send + Sync + 'static>(mut self, source: E) -> Self {
+        self.source = Some(Box::new(source));
+        self
+    }
 
-        let mut prev_time = Instant::now();
+    // Filled in by the scheduler, so systems don't have to name themselves:
+    pub fn in_system(mut self, system: &'static str) -> Self {
+        self.system = Some(system);
+        self
+    }
 
-        loop {
-            let now = Instant::now();
-            let delta = now - prev_time;
-            prev_time = now;
+    pub fn system(&self) -> Option<&'static str> {
+        self.system
+    }
 
-            if let Ok(res) = self.on_freeze() {
-                self.on_run(res, delta);
-            }
+    pub fn cause(&self) -> &str {
+        &self.cause
+    }
+}
 
-            std::thread::sleep_ms(10);
-        }
+impl Error for SystemRuntimeError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        self.source.as_ref().map(|source| &**source as &(dyn Error + 'static))
     }
 }
 
-pub struct SystemRuntimeError(&'static str);
-impl Error for SystemRuntimeError {}
 impl Display for SystemRuntimeError {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
-        writeln!(f, "System runtime error")
+        match self.system {
+            Some(system) => write!(f, "{}: {}", system, self.cause)?,
+            None => write!(f, "{}", self.cause)?
+        }
+
+        match self.source {
+            Some(ref source) => write!(f, " (caused by: {})", source),
+            None => Ok(())
+        }
     }
 }
+
 impl Debug for SystemRuntimeError {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
-        writeln!(f, "")
+        write!(f, "SystemRuntimeError: {}", self)
     }
 }
 
-pub fn start_system_in_parallel<T1, T2>(environment: T2)
-    where T1: for<'a> System<'a> + 'static,
-          T2: 'static + EntityContainer {
+// How a failing system is retried. Delays are measured in simulation time, so retries happen
+// on the same ticks in every run.
+#[derive(Copy, Clone, Debug)]
+pub struct RetryPolicy {
+    // None retries forever:
+    pub max_retries: Option<u32>,
+    pub initial_delay: Duration,
+    pub max_delay: Duration,
+    pub backoff_factor: f32
+}
 
-    let env_copy = environment.clone();
-    thread::spawn(  move || { T1::new().start(env_copy); });
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            max_retries: None,
+            initial_delay: Duration::from_secs(1),
+            max_delay: Duration::from_secs(30),
+            backoff_factor: 2.0
+        }
+    }
+}
+
+impl RetryPolicy {
+    // Gives up on the first failure:
+    pub fn never() -> Self {
+        RetryPolicy { max_retries: Some(0), ..Self::default() }
+    }
+
+    // The delay before the given retry, counting from 1:
+    pub fn delay(&self, retry: u32) -> Duration {
+        let factor = self.backoff_factor.max(1.0).powi(retry.saturating_sub(1) as i32);
+        let delay = self.initial_delay.as_secs_f32() * factor;
+
+        Duration::from_secs_f32(delay.min(self.max_delay.as_secs_f32()))
+    }
+
+    pub fn allows(&self, retry: u32) -> bool {
+        self.max_retries.map_or(true, |max_retries| retry <= max_retries)
+    }
 }
@@ -0,0 +1,961 @@
+use std::collections::{BTreeSet, HashMap};
+use std::error::Error;
+use std::fmt::{self, Display, Formatter};
+use std::time::{Duration, Instant};
+use rayon::{ThreadPool, ThreadPoolBuilder};
+use rayon::prelude::*;
+use crate::world::entity::Entity;
+use crate::world::entity::event::{EventBus, Subscription, WorldEvent};
+use crate::world::resource::Resources;
+use crate::util::profiler::{Profiler, TimingStats, DEFAULT_WINDOW};
+use crate::world::system::{System, SystemRuntimeError, Stage, SystemId, SystemAccess, RetryPolicy};
+
+pub const DEFAULT_TIMESTEP: Duration = Duration::from_millis(10);
+
+// Never catch up on more ticks than this in one update, otherwise a slow tick makes the next
+// update even slower.
+const MAX_TICKS_PER_UPDATE: u32 = 25;
+
+// Bounds of the time scale. Slower than this is better done by pausing and stepping, and faster
+// only makes the scheduler fall behind:
+pub const MIN_TIME_SCALE: f32 = 1.0 / 64.0;
+pub const MAX_TIME_SCALE: f32 = 64.0;
+
+// World resource, advanced by the scheduler after every tick:
+#[derive(Copy, Clone, Debug)]
+pub struct SimulationTime {
+    pub tick: u64,
+    pub elapsed: Duration,
+    pub timestep: Duration,
+    // The moment in real time the end of the running tick stands for. Ticks run in bursts, so
+    // this is usually a little earlier than the moment the tick actually runs.
+    pub wall_time: Instant
+}
+
+// World resource controlling how the scheduler spends real time. While paused, no ticks run
+// except for those requested through step:
+#[derive(Copy, Clone, Debug)]
+pub struct SimulationClock {
+    paused: bool,
+    time_scale: f32,
+    pending_steps: u32
+}
+
+impl Default for SimulationClock {
+    fn default() -> Self {
+        SimulationClock { paused: false, time_scale: 1.0, pending_steps: 0 }
+    }
+}
+
+impl SimulationClock {
+    pub fn pause(&mut self) {
+        self.paused = true;
+    }
+
+    pub fn resume(&mut self) {
+        self.paused = false;
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    // Runs the given number of ticks on the next update, even when paused:
+    pub fn step(&mut self, n_ticks: u32) {
+        self.pending_steps += n_ticks;
+    }
+
+    // Scales real time before it is handed to the scheduler; the timestep itself never changes.
+    // The scale is clamped to [MIN_TIME_SCALE, MAX_TIME_SCALE], and NaN is ignored:
+    pub fn set_time_scale(&mut self, time_scale: f32) {
+        if !time_scale.is_nan() {
+            self.time_scale = time_scale.max(MIN_TIME_SCALE).min(MAX_TIME_SCALE);
+        }
+    }
+
+    pub fn time_scale(&self) -> f32 {
+        self.time_scale
+    }
+}
+
+// Type-erased system, so that systems with different environments can share a schedule.
+pub trait SystemRunner: Send + Sync {
+    fn start(&mut self, resources: &Resources);
+
+    fn stop(&mut self, resources: &Resources);
+
+    fn fetch(&mut self, root: &Entity, resources: &Resources) -> Result<(), SystemRuntimeError>;
+
+    fn notify(&mut self, event: &WorldEvent) -> bool;
+
+    fn run(&mut self, delta: Duration) -> Result<(), SystemRuntimeError>;
+}
+
+struct SystemSlot<S> {
+    system: S
+}
+
+impl<S> SystemRunner for SystemSlot<S> where S: for<'a> System<'a> + 'static {
+    fn start(&mut self, resources: &Resources) {
+        self.system.on_start(resources);
+    }
+
+    fn stop(&mut self, resources: &Resources) {
+        self.system.on_stop(resources);
+    }
+
+    fn fetch(&mut self, root: &Entity, resources: &Resources) -> Result<(), SystemRuntimeError> {
+        self.system.on_fetch(root, resources)
+    }
+
+    fn notify(&mut self, event: &WorldEvent) -> bool {
+        self.system.on_event(event)
+    }
+
+    fn run(&mut self, delta: Duration) -> Result<(), SystemRuntimeError> {
+        let environment = self.system.on_freeze()?;
+        self.system.on_run(environment, delta);
+        Ok(())
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+enum Lifecycle {
+    // Started on its next tick:
+    Pending,
+    Running,
+    Stopped
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum SystemState {
+    Pending,
+    Running,
+    // Waiting for the given retry, which happens at the given simulation time:
+    Retrying { retry: u32, at: Duration },
+    // Out of retries; the system stays idle until it is started again:
+    Failed,
+    Stopped
+}
+
+// A snapshot of how a system is doing, as reported by World::system_status:
+#[derive(Clone, Debug)]
+pub struct SystemStatus {
+    pub name: &'static str,
+    pub stage: Stage,
+    pub state: SystemState,
+    // Number of successful runs:
+    pub ticks: u64,
+    pub failures: u32,
+    pub last_error: Option<String>,
+    // Over the profiler's rolling window:
+    pub timing: Option<TimingStats>
+}
+
+impl Display for SystemStatus {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        // Without the module path, which is too long for the HUD:
+        let name = self.name.rsplit("::").next().unwrap_or(self.name);
+
+        match self.state {
+            SystemState::Pending => write!(f, "{}: pending", name)?,
+            SystemState::Running => write!(f, "{}: running", name)?,
+            SystemState::Retrying { retry, at } => write!(f, "{}: retry #{} at {:.1}s", name, retry, at.as_secs_f32())?,
+            SystemState::Failed => write!(f, "{}: failed", name)?,
+            SystemState::Stopped => write!(f, "{}: stopped", name)?
+        }
+
+        write!(f, " ({} ticks)", self.ticks)?;
+
+        if let Some(ref timing) = self.timing {
+            write!(f, " {:.3}ms avg, {:.3}ms max", timing.avg.as_secs_f64() * 1000.0, timing.max.as_secs_f64() * 1000.0)?;
+        }
+
+        match self.last_error {
+            Some(ref e) if self.state != SystemState::Running => write!(f, " - {}", e),
+            _ => Ok(())
+        }
+    }
+}
+
+struct ScheduledSystem {
+    id: SystemId,
+    stage: Stage,
+    before: Vec<SystemId>,
+    after: Vec<SystemId>,
+    access: SystemAccess,
+    retry_policy: RetryPolicy,
+    runner: Box<dyn SystemRunner>,
+    lifecycle: Lifecycle,
+    // Whether the members have to be fetched before the next run:
+    stale: bool,
+    // Failures since the last successful run, and when to try again (None once out of retries):
+    failures: u32,
+    retry_at: Option<Duration>,
+    ticks: u64,
+    total_failures: u32,
+    last_error: Option<String>
+}
+
+impl ScheduledSystem {
+    fn tick(&mut self, root: &Entity, resources: &Resources, events: &[WorldEvent], now: Duration, timestep: Duration, profiler: &Profiler) {
+        if self.lifecycle == Lifecycle::Pending {
+            self.runner.start(resources);
+            self.lifecycle = Lifecycle::Running;
+        }
+
+        if !self.stale {
+            let runner = &mut self.runner;
+            self.stale = !events.iter().all(|event| runner.notify(event));
+        }
+
+        if self.failures > 0 {
+            match self.retry_at {
+                Some(retry_at) if now >= retry_at => {},
+                _ => return
+            }
+        }
+
+        let start = Instant::now();
+
+        let result = if self.stale {
+            self.runner.fetch(root, resources).map(|()| self.stale = false)
+        } else {
+            Ok(())
+        };
+
+        let result = result.and_then(|()| self.runner.run(timestep));
+        profiler.record(self.id.name(), "system", start, start.elapsed());
+
+        match result {
+            Ok(()) => {
+                self.failures = 0;
+                self.retry_at = None;
+                self.ticks += 1;
+            },
+            Err(e) => self.fail(e.in_system(self.id.name()), now)
+        }
+    }
+
+    fn fail(&mut self, e: SystemRuntimeError, now: Duration) {
+        self.failures += 1;
+        self.total_failures += 1;
+        // Whatever went wrong, the members may have to be fetched again:
+        self.stale = true;
+
+        self.retry_at = if self.retry_policy.allows(self.failures) {
+            let delay = self.retry_policy.delay(self.failures);
+            warn!("{}; Retry #{} in {:?}", e, self.failures, delay);
+            Some(now + delay)
+        } else {
+            error!("{}; Giving up", e);
+            None
+        };
+
+        self.last_error = Some(e.to_string());
+    }
+
+    fn stop(&mut self, resources: &Resources) {
+        if self.lifecycle == Lifecycle::Running {
+            self.runner.stop(resources);
+        }
+
+        self.lifecycle = Lifecycle::Stopped;
+    }
+
+    // Starts a stopped system, or gives a system that ran out of retries another chance:
+    fn start(&mut self) {
+        let failed = self.failures > 0 && self.retry_at.is_none();
+
+        if self.lifecycle == Lifecycle::Stopped {
+            self.lifecycle = Lifecycle::Pending;
+        }
+        else if !failed {
+            return;
+        }
+
+        self.failures = 0;
+        self.retry_at = None;
+        // Events were missed while stopped or failed:
+        self.stale = true;
+    }
+
+    fn status(&self, profiler: &Profiler) -> SystemStatus {
+        let state = match self.lifecycle {
+            Lifecycle::Pending => SystemState::Pending,
+            Lifecycle::Stopped => SystemState::Stopped,
+            Lifecycle::Running if self.failures == 0 => SystemState::Running,
+            Lifecycle::Running => match self.retry_at {
+                Some(at) => SystemState::Retrying { retry: self.failures, at },
+                None => SystemState::Failed
+            }
+        };
+
+        SystemStatus {
+            name: self.id.name(),
+            stage: self.stage,
+            state,
+            ticks: self.ticks,
+            failures: self.total_failures,
+            last_error: self.last_error.clone(),
+            timing: profiler.stats_of(self.id.name())
+        }
+    }
+}
+
+// Steps all registered systems stage by stage with a fixed timestep. Within a stage, systems
+// run in the order their before/after constraints dictate, falling back to registration order.
+// Systems that are unordered relative to each other and whose access doesn't conflict are
+// batched and run concurrently on the scheduler's thread pool; as they can't observe each
+// other, the outcome is the same as running them one by one. Batching keeps systems from
+// contending for locks, but they still take them, see SystemAccess. Real time is collected in
+// an accumulator and spent in whole ticks, so the outcome of a simulation only depends on the
+// number of ticks and never on how fast the host happens to be.
+pub struct Scheduler {
+    root: Entity,
+    resources: Resources,
+    systems: Vec<ScheduledSystem>,
+    events: Subscription,
+    // Indices into `systems`, in batches to run one after another; None until the schedule has
+    // been validated:
+    batches: Option<Vec<Vec<usize>>>,
+    pool: ThreadPool,
+    profiler: Profiler,
+    timestep: Duration,
+    accumulator: Duration,
+    // Simulation time, i.e. the sum of all timesteps run so far:
+    elapsed: Duration
+}
+
+impl Scheduler {
+    pub fn new(root: Entity, resources: Resources, timestep: Duration) -> Self {
+        resources.insert(SimulationTime { tick: 0, elapsed: Duration::new(0, 0), timestep, wall_time: Instant::now() });
+        if !resources.contains::<SimulationClock>() {
+            resources.insert(SimulationClock::default());
+        }
+
+        // Shared with whoever else records timings, e.g. the renderer:
+        let profiler = match resources.fetch::<Profiler>().and_then(|profiler| profiler.peek(Profiler::clone)) {
+            Some(profiler) => profiler,
+            None => {
+                let profiler = Profiler::new(DEFAULT_WINDOW);
+                resources.insert(profiler.clone());
+                profiler
+            }
+        };
+
+        // A root that doesn't belong to a world never changes, so there is nothing to listen to:
+        let events = root.registry().map_or_else(EventBus::new, |registry| registry.events().clone()).subscribe();
+
+        Self {
+            root,
+            resources,
+            systems: Vec::new(),
+            events,
+            batches: None,
+            pool: ThreadPoolBuilder::new()
+                .thread_name(|i| format!("system-worker-{}", i))
+                .build()
+                .expect("Couldn't create system thread pool!"),
+            profiler,
+            timestep,
+            accumulator: Duration::new(0, 0),
+            elapsed: Duration::new(0, 0)
+        }
+    }
+
+    pub fn register<S>(&mut self) where S: for<'a> System<'a> + 'static {
+        self.systems.push(ScheduledSystem {
+            id: SystemId::of::<S>(),
+            stage: S::stage(),
+            before: S::before(),
+            after: S::after(),
+            access: S::access(),
+            retry_policy: S::retry_policy(),
+            runner: Box::new(SystemSlot { system: S::new() }),
+            lifecycle: Lifecycle::Pending,
+            stale: true,
+            failures: 0,
+            retry_at: None,
+            ticks: 0,
+            total_failures: 0,
+            last_error: None
+        });
+
+        self.batches = None;
+    }
+
+    // Stopped systems keep their place in the schedule, but are skipped until started again.
+    // Returns false if the system isn't registered.
+    pub fn stop_system<S>(&mut self) -> bool where S: for<'a> System<'a> + 'static {
+        let resources = &self.resources;
+
+        self.systems
+            .iter_mut()
+            .find(|scheduled| scheduled.id == SystemId::of::<S>())
+            .map(|scheduled| scheduled.stop(resources))
+            .is_some()
+    }
+
+    // Also restarts a system that failed for good. Returns false if the system isn't registered.
+    pub fn start_system<S>(&mut self) -> bool where S: for<'a> System<'a> + 'static {
+        self.systems
+            .iter_mut()
+            .find(|scheduled| scheduled.id == SystemId::of::<S>())
+            .map(|scheduled| scheduled.start())
+            .is_some()
+    }
+
+    pub fn is_running<S>(&self) -> bool where S: for<'a> System<'a> + 'static {
+        self.systems
+            .iter()
+            .any(|scheduled| scheduled.id == SystemId::of::<S>() && scheduled.lifecycle != Lifecycle::Stopped)
+    }
+
+    // In registration order:
+    pub fn system_status(&self) -> Vec<SystemStatus> {
+        self.systems
+            .iter()
+            .map(|scheduled| scheduled.status(&self.profiler))
+            .collect()
+    }
+
+    // Stops all systems, last scheduled first:
+    pub fn shutdown(&mut self) {
+        let resources = &self.resources;
+        let systems = &mut self.systems;
+
+        if let Some(ref batches) = self.batches {
+            for &i in batches.iter().flatten().rev() {
+                systems[i].stop(resources);
+            }
+        }
+
+        for scheduled in systems.iter_mut() {
+            scheduled.stop(resources);
+        }
+    }
+
+    // Orders the registered systems, failing if their constraints contradict each other:
+    pub fn schedule(&mut self) -> Result<(), ScheduleError> {
+        let indices: HashMap<SystemId, usize> = self.systems
+            .iter()
+            .enumerate()
+            .map(|(i, scheduled)| (scheduled.id, i))
+            .collect();
+
+        // Edges point from a system to the systems that have to run after it:
+        let mut successors: Vec<Vec<usize>> = vec![Vec::new(); self.systems.len()];
+        let mut predecessors: Vec<Vec<usize>> = vec![Vec::new(); self.systems.len()];
+
+        for (i, scheduled) in self.systems.iter().enumerate() {
+            let before = scheduled.before.iter().filter_map(|id| indices.get(id)).map(|&j| (i, j));
+            let after = scheduled.after.iter().filter_map(|id| indices.get(id)).map(|&j| (j, i));
+
+            for (first, then) in before.chain(after) {
+                let (first_stage, then_stage) = (self.systems[first].stage, self.systems[then].stage);
+
+                if first_stage > then_stage {
+                    return Err(ScheduleError::StageOrder(self.systems[first].id.name(), self.systems[then].id.name()));
+                }
+
+                // Systems in different stages are already ordered by their stage:
+                if first_stage == then_stage && !successors[first].contains(&then) {
+                    successors[first].push(then);
+                    predecessors[then].push(first);
+                }
+            }
+        }
+
+        // Kahn's algorithm, always picking the ready system of the earliest stage that was
+        // registered first, so that the order is stable:
+        let mut in_degree: Vec<usize> = predecessors.iter().map(Vec::len).collect();
+        let mut ready: BTreeSet<(Stage, usize)> = (0..self.systems.len())
+            .filter(|&i| in_degree[i] == 0)
+            .map(|i| (self.systems[i].stage, i))
+            .collect();
+
+        let mut order = Vec::with_capacity(self.systems.len());
+        while let Some(&next) = ready.iter().next() {
+            ready.remove(&next);
+
+            let (_, i) = next;
+            order.push(i);
+
+            for &j in &successors[i] {
+                in_degree[j] -= 1;
+                if in_degree[j] == 0 {
+                    ready.insert((self.systems[j].stage, j));
+                }
+            }
+        }
+
+        if order.len() < self.systems.len() {
+            return Err(ScheduleError::Cycle(self.find_cycle(&in_degree, &predecessors)));
+        }
+
+        // A system goes in the first batch after everything it has to wait for: earlier stages,
+        // systems it is constrained to run after and earlier systems it conflicts with.
+        let mut levels = vec![0; self.systems.len()];
+        let mut batches: Vec<Vec<usize>> = Vec::new();
+
+        for (pos, &i) in order.iter().enumerate() {
+            let level = order[..pos]
+                .iter()
+                .filter(|&&j| {
+                    self.systems[j].stage < self.systems[i].stage ||
+                        predecessors[i].contains(&j) ||
+                        self.systems[j].access.conflicts_with(&self.systems[i].access)
+                })
+                .map(|&j| levels[j] + 1)
+                .max()
+                .unwrap_or(0);
+
+            levels[i] = level;
+            if batches.len() <= level {
+                batches.resize(level + 1, Vec::new());
+            }
+            batches[level].push(i);
+        }
+
+        self.batches = Some(batches);
+        Ok(())
+    }
+
+    // Every system left over by the sort still waits on another left-over system, so walking
+    // backwards from any of them eventually runs in circles:
+    fn find_cycle(&self, in_degree: &[usize], predecessors: &[Vec<usize>]) -> Vec<&'static str> {
+        let start = match (0..in_degree.len()).find(|&i| in_degree[i] > 0) {
+            Some(start) => start,
+            None => return Vec::new()
+        };
+
+        let mut path = vec![start];
+        loop {
+            let current = *path.last().unwrap();
+            let previous = predecessors[current]
+                .iter()
+                .cloned()
+                .find(|&j| in_degree[j] > 0)
+                .expect("Left-over system without left-over predecessor!");
+
+            if let Some(pos) = path.iter().position(|&i| i == previous) {
+                // The path was walked backwards, so flip it to read in execution order:
+                return path[pos..].iter().rev().map(|&i| self.systems[i].id.name()).collect();
+            }
+
+            path.push(previous);
+        }
+    }
+
+    pub fn timestep(&self) -> Duration {
+        self.timestep
+    }
+
+    pub fn set_timestep(&mut self, timestep: Duration) {
+        self.timestep = timestep;
+        if let Some(time) = self.resources.fetch::<SimulationTime>() {
+            time.peek_mut(|time| time.timestep = timestep);
+        }
+    }
+
+    // Advances the simulation by however many whole ticks fit into the (scaled) elapsed time,
+    // carrying over the remainder. Returns the number of ticks that were run, or why the systems
+    // couldn't be scheduled.
+    pub fn update(&mut self, elapsed: Duration) -> Result<u32, ScheduleError> {
+        let clock = self.resources
+            .fetch::<SimulationClock>()
+            .and_then(|clock| clock.peek_mut(|clock| {
+                let snapshot = *clock;
+                clock.pending_steps = 0;
+                snapshot
+            }))
+            .unwrap_or_default();
+
+        for _ in 0..clock.pending_steps {
+            self.tick()?;
+        }
+
+        if clock.paused {
+            self.accumulator = Duration::new(0, 0);
+            return Ok(clock.pending_steps);
+        }
+
+        // Time that can't be represented can't be caught up on either:
+        self.accumulator += scale_duration(elapsed, clock.time_scale).unwrap_or(Duration::new(0, 0));
+
+        let now = Instant::now();
+        let mut ticks = clock.pending_steps;
+        while self.accumulator >= self.timestep {
+            if ticks >= MAX_TICKS_PER_UPDATE {
+                warn!("Scheduler: Falling behind, skipping {:?} of simulation time", self.accumulator);
+                self.accumulator = Duration::new(0, 0);
+                break;
+            }
+
+            self.accumulator -= self.timestep;
+
+            // Whatever is left in the accumulator is how far real time has moved past this tick:
+            let ahead = if clock.time_scale > f32::EPSILON {
+                scale_duration(self.accumulator, 1.0 / clock.time_scale)
+            } else {
+                None
+            };
+
+            self.tick_at(ahead.and_then(|ahead| now.checked_sub(ahead)).unwrap_or(now))?;
+            ticks += 1;
+        }
+
+        Ok(ticks)
+    }
+
+    pub fn tick(&mut self) -> Result<(), ScheduleError> {
+        self.tick_at(Instant::now())
+    }
+
+    // Systems registered since the last tick are scheduled first; if that fails, nothing runs:
+    fn tick_at(&mut self, wall_time: Instant) -> Result<(), ScheduleError> {
+        if self.batches.is_none() {
+            if let Err(e) = self.schedule() {
+                error!("Scheduler: {}", e);
+                return Err(e);
+            }
+        }
+
+        if let Some(time) = self.resources.fetch::<SimulationTime>() {
+            time.peek_mut(|time| time.wall_time = wall_time);
+        }
+
+        // Everything that changed since the last tick, for the systems to patch their members with:
+        let tick_start = Instant::now();
+        let events: Vec<WorldEvent> = match self.events.drain() {
+            Some(events) => events,
+            // Some changes were missed, so all members have to be fetched again:
+            None => {
+                self.systems.iter_mut().for_each(|scheduled| scheduled.stale = true);
+                Vec::new()
+            }
+        };
+        let (root, resources, events) = (&self.root, &self.resources, &events[..]);
+        let (now, timestep, profiler) = (self.elapsed, self.timestep, &self.profiler);
+
+        for batch in self.batches.as_ref().unwrap() {
+            let mut members: Vec<&mut ScheduledSystem> = self.systems
+                .iter_mut()
+                .enumerate()
+                .filter(|(i, scheduled)| batch.contains(i) && scheduled.lifecycle != Lifecycle::Stopped)
+                .map(|(_, scheduled)| scheduled)
+                .collect();
+
+            if members.len() <= 1 {
+                members.iter_mut().for_each(|scheduled| scheduled.tick(root, resources, events, now, timestep, profiler));
+            } else {
+                self.pool.install(|| {
+                    members
+                        .par_iter_mut()
+                        .for_each(|scheduled| scheduled.tick(root, resources, events, now, timestep, profiler));
+                });
+            }
+        }
+
+        self.profiler.record("Scheduler::tick", "tick", tick_start, tick_start.elapsed());
+
+        self.elapsed += timestep;
+        if let Some(time) = self.resources.fetch::<SimulationTime>() {
+            time.peek_mut(|time| {
+                time.tick += 1;
+                time.elapsed += timestep;
+            });
+        }
+
+        Ok(())
+    }
+}
+
+// Like Duration::mul_f32, but returns None instead of panicking when the result is negative,
+// not a number or too large for a Duration:
+fn scale_duration(duration: Duration, factor: f32) -> Option<Duration> {
+    let secs = duration.as_secs_f64() * factor as f64;
+
+    if secs.is_finite() && secs >= 0.0 && secs < u64::MAX as f64 {
+        Some(Duration::from_secs_f64(secs))
+    } else {
+        None
+    }
+}
+
+pub enum ScheduleError {
+    // The systems involved, in the order they would have to run in:
+    Cycle(Vec<&'static str>),
+    // The first system has to run before the second one, but is in a later stage:
+    StageOrder(&'static str, &'static str)
+}
+
+impl Error for ScheduleError {}
+
+impl Display for ScheduleError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            ScheduleError::Cycle(systems) => write!(f, "Systems depend on each other in a cycle: {} -> {}", systems.join(" -> "), systems.first().unwrap_or(&"?")),
+            ScheduleError::StageOrder(first, then) => write!(f, "System {} can't run before {}, as it is in a later stage", first, then)
+        }
+    }
+}
+
+impl fmt::Debug for ScheduleError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "ScheduleError: {}", self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::entity::EntityContainer;
+    use crate::world::resource::ResourceManager;
+
+    struct Position;
+    struct Velocity;
+
+    macro_rules! test_system {
+        ($name:ident, $access:expr) => {
+            test_system!($name, $access, Vec::new());
+        };
+        ($name:ident, $access:expr, $after:expr) => {
+            struct $name;
+
+            impl<'a> System<'a> for $name {
+                type Environment = ();
+
+                fn new() -> Self {
+                    $name
+                }
+
+                fn after() -> Vec<SystemId> {
+                    $after
+                }
+
+                fn access() -> SystemAccess {
+                    $access
+                }
+
+                fn on_fetch<T: EntityContainer>(&mut self, _source: &T, _resources: &Resources) -> Result<(), SystemRuntimeError> {
+                    Ok(())
+                }
+
+                fn on_freeze(&'a self) -> Result<Self::Environment, SystemRuntimeError> {
+                    Ok(())
+                }
+
+                fn on_run(&self, _environment: Self::Environment, _delta: Duration) {}
+            }
+        };
+    }
+
+    test_system!(MovePositions, SystemAccess::new().write::<Position>());
+    test_system!(MoveVelocities, SystemAccess::new().write::<Velocity>());
+    test_system!(ReadPositions, SystemAccess::new().read::<Position>());
+    test_system!(ReadVelocities, SystemAccess::new().read::<Velocity>());
+    test_system!(ReadBoth, SystemAccess::new().read::<Position>().read::<Velocity>());
+    test_system!(TouchAnything, SystemAccess::exclusive());
+    test_system!(Chicken, SystemAccess::new(), vec![SystemId::of::<Egg>()]);
+    test_system!(Egg, SystemAccess::new(), vec![SystemId::of::<Chicken>()]);
+
+    fn batches(scheduler: &mut Scheduler) -> Vec<Vec<&'static str>> {
+        scheduler.schedule().expect("Couldn't schedule systems!");
+
+        scheduler.batches
+            .as_ref()
+            .unwrap()
+            .iter()
+            .map(|batch| batch.iter().map(|&i| scheduler.systems[i].id.name()).collect())
+            .collect()
+    }
+
+    #[test]
+    fn disjoint_systems_share_a_batch() {
+        let mut scheduler = Scheduler::new(Entity::new("root"), Resources::new(), DEFAULT_TIMESTEP);
+        scheduler.register::<MovePositions>();
+        scheduler.register::<MoveVelocities>();
+
+        assert_eq!(batches(&mut scheduler), vec![vec![SystemId::of::<MovePositions>().name(), SystemId::of::<MoveVelocities>().name()]]);
+    }
+
+    #[test]
+    fn conflicting_systems_run_one_after_another() {
+        let mut scheduler = Scheduler::new(Entity::new("root"), Resources::new(), DEFAULT_TIMESTEP);
+        scheduler.register::<MovePositions>();
+        scheduler.register::<MoveVelocities>();
+        scheduler.register::<ReadPositions>();
+        scheduler.register::<TouchAnything>();
+
+        assert_eq!(batches(&mut scheduler), vec![
+            vec![SystemId::of::<MovePositions>().name(), SystemId::of::<MoveVelocities>().name()],
+            vec![SystemId::of::<ReadPositions>().name()],
+            vec![SystemId::of::<TouchAnything>().name()]
+        ]);
+    }
+
+    // Systems in the same stage and without before/after constraints:
+    #[test]
+    fn batches_hold_no_conflicts_and_run_systems_as_early_as_possible() {
+        let mut scheduler = Scheduler::new(Entity::new("root"), Resources::new(), DEFAULT_TIMESTEP);
+        scheduler.register::<ReadPositions>();
+        scheduler.register::<MovePositions>();
+        scheduler.register::<ReadBoth>();
+        scheduler.register::<TouchAnything>();
+        scheduler.register::<ReadVelocities>();
+        scheduler.register::<MoveVelocities>();
+        scheduler.schedule().expect("Couldn't schedule systems!");
+
+        let batches = scheduler.batches.clone().unwrap();
+        let conflict = |i: usize, j: usize| scheduler.systems[i].access.conflicts_with(&scheduler.systems[j].access);
+
+        assert_eq!(batches.iter().map(Vec::len).sum::<usize>(), scheduler.systems.len());
+
+        for (level, batch) in batches.iter().enumerate() {
+            for (pos, &i) in batch.iter().enumerate() {
+                assert!(batch[pos + 1..].iter().all(|&j| !conflict(i, j)));
+
+                // Otherwise it would have gone into the batch before:
+                if level > 0 {
+                    assert!(batches[level - 1].iter().any(|&j| conflict(i, j)));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn broken_schedules_fail_every_tick() {
+        let mut scheduler = Scheduler::new(Entity::new("root"), Resources::new(), DEFAULT_TIMESTEP);
+        scheduler.register::<Chicken>();
+        scheduler.register::<Egg>();
+
+        assert!(matches!(scheduler.tick(), Err(ScheduleError::Cycle(_))));
+        assert!(matches!(scheduler.update(DEFAULT_TIMESTEP * 2), Err(ScheduleError::Cycle(_))));
+        assert_eq!(scheduler.resources.fetch::<SimulationTime>().unwrap().lock_resource_for_read().tick, 0);
+    }
+
+    fn ticks(scheduler: &Scheduler) -> u64 {
+        scheduler.resources.fetch::<SimulationTime>().unwrap().lock_resource_for_read().tick
+    }
+
+    fn clock(scheduler: &Scheduler) -> ResourceManager<SimulationClock> {
+        scheduler.resources.fetch::<SimulationClock>().unwrap()
+    }
+
+    #[test]
+    fn paused_clocks_only_run_requested_steps() {
+        let mut scheduler = Scheduler::new(Entity::new("root"), Resources::new(), DEFAULT_TIMESTEP);
+        scheduler.register::<MovePositions>();
+
+        clock(&scheduler).lock_resource_for_write().pause();
+        assert_eq!(scheduler.update(DEFAULT_TIMESTEP * 10).unwrap(), 0);
+
+        clock(&scheduler).lock_resource_for_write().step(3);
+        assert_eq!(scheduler.update(DEFAULT_TIMESTEP * 10).unwrap(), 3);
+        assert_eq!(scheduler.update(DEFAULT_TIMESTEP * 10).unwrap(), 0);
+        assert_eq!(ticks(&scheduler), 3);
+
+        // Time that passed while paused isn't caught up on:
+        clock(&scheduler).lock_resource_for_write().resume();
+        assert_eq!(scheduler.update(DEFAULT_TIMESTEP * 2).unwrap(), 2);
+        assert_eq!(ticks(&scheduler), 5);
+    }
+
+    #[test]
+    fn time_scale_is_clamped() {
+        let mut clock = SimulationClock::default();
+
+        clock.set_time_scale(1000.0);
+        assert_eq!(clock.time_scale(), MAX_TIME_SCALE);
+        clock.set_time_scale(0.0);
+        assert_eq!(clock.time_scale(), MIN_TIME_SCALE);
+        clock.set_time_scale(-2.0);
+        assert_eq!(clock.time_scale(), MIN_TIME_SCALE);
+        clock.set_time_scale(f32::NAN);
+        assert_eq!(clock.time_scale(), MIN_TIME_SCALE);
+        clock.set_time_scale(f32::INFINITY);
+        assert_eq!(clock.time_scale(), MAX_TIME_SCALE);
+        clock.set_time_scale(2.0);
+        assert_eq!(clock.time_scale(), 2.0);
+    }
+
+    #[test]
+    fn time_scale_stretches_real_time() {
+        let mut scheduler = Scheduler::new(Entity::new("root"), Resources::new(), DEFAULT_TIMESTEP);
+        scheduler.register::<MovePositions>();
+
+        clock(&scheduler).lock_resource_for_write().set_time_scale(2.0);
+        assert_eq!(scheduler.update(DEFAULT_TIMESTEP * 3).unwrap(), 6);
+
+        clock(&scheduler).lock_resource_for_write().set_time_scale(0.5);
+        assert_eq!(scheduler.update(DEFAULT_TIMESTEP * 3).unwrap(), 1);
+        assert_eq!(scheduler.update(DEFAULT_TIMESTEP).unwrap(), 1);
+    }
+
+    // Records its hooks and runs in the Calls resource:
+    struct Calls(Vec<&'static str>);
+
+    struct Tracked {
+        calls: Option<ResourceManager<Calls>>
+    }
+
+    impl<'a> System<'a> for Tracked {
+        type Environment = Option<&'a ResourceManager<Calls>>;
+
+        fn new() -> Self {
+            Tracked { calls: None }
+        }
+
+        fn access() -> SystemAccess {
+            SystemAccess::new().write::<Calls>()
+        }
+
+        fn on_start(&mut self, resources: &Resources) {
+            resources.fetch::<Calls>().unwrap().lock_resource_for_write().0.push("start");
+        }
+
+        fn on_stop(&mut self, resources: &Resources) {
+            resources.fetch::<Calls>().unwrap().lock_resource_for_write().0.push("stop");
+        }
+
+        fn on_fetch<T: EntityContainer>(&mut self, _source: &T, resources: &Resources) -> Result<(), SystemRuntimeError> {
+            self.calls = resources.fetch::<Calls>();
+            Ok(())
+        }
+
+        fn on_freeze(&'a self) -> Result<Self::Environment, SystemRuntimeError> {
+            Ok(self.calls.as_ref())
+        }
+
+        fn on_run(&self, calls: Self::Environment, _delta: Duration) {
+            calls.unwrap().lock_resource_for_write().0.push("run");
+        }
+    }
+
+    #[test]
+    fn stopped_systems_skip_ticks_until_started_again() {
+        let resources = Resources::new();
+        resources.insert(Calls(Vec::new()));
+
+        let mut scheduler = Scheduler::new(Entity::new("root"), resources.clone(), DEFAULT_TIMESTEP);
+        scheduler.register::<Tracked>();
+        assert!(!scheduler.stop_system::<MovePositions>());
+
+        scheduler.tick().unwrap();
+        assert!(scheduler.stop_system::<Tracked>());
+        assert!(!scheduler.is_running::<Tracked>());
+        scheduler.tick().unwrap();
+        assert_eq!(scheduler.system_status()[0].state, SystemState::Stopped);
+
+        assert!(scheduler.start_system::<Tracked>());
+        assert_eq!(scheduler.system_status()[0].state, SystemState::Pending);
+        scheduler.tick().unwrap();
+        scheduler.shutdown();
+        scheduler.shutdown();
+
+        assert_eq!(resources.fetch::<Calls>().unwrap().lock_resource_for_read().0, vec!["start", "run", "stop", "start", "run", "stop"]);
+        assert_eq!(scheduler.system_status()[0].ticks, 2);
+    }
+}
@@ -0,0 +1,227 @@
+use crate::world::system::{System, SystemRuntimeError, Stage, SystemAccess};
+use crate::world::system::members::MemberList;
+use crate::world::system::scheduler::{SimulationTime, SimulationClock};
+use crate::world::resource::{Resources, ResourceManager};
+use crate::world::entity::component::model::GraphicsModel;
+use crate::world::entity::component::transform::Transform;
+use crate::world::entity::registry::EntityId;
+use crate::world::entity::event::WorldEvent;
+use crate::world::entity::EntityContainer;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::mem;
+use std::time::{Duration, Instant};
+
+// The absolute transforms of all graphics models at the end of a tick:
+pub struct Snapshot {
+    // Simulation time:
+    pub time: Duration,
+    // The moment in real time the snapshot stands for, and how fast simulation time ran then:
+    pub wall_time: Instant,
+    pub time_scale: f32,
+    pub transforms: HashMap<EntityId, Transform>
+}
+
+impl Snapshot {
+    fn empty() -> Self {
+        Snapshot {
+            time: Duration::new(0, 0),
+            wall_time: Instant::now(),
+            time_scale: 1.0,
+            transforms: HashMap::new()
+        }
+    }
+}
+
+// World resource holding the last two snapshots, so that the renderer can interpolate between
+// them without ever seeing a tick half-way through.
+pub struct TransformSnapshots {
+    previous: Arc<Snapshot>,
+    current: Arc<Snapshot>
+}
+
+impl Default for TransformSnapshots {
+    fn default() -> Self {
+        TransformSnapshots {
+            previous: Arc::new(Snapshot::empty()),
+            current: Arc::new(Snapshot::empty())
+        }
+    }
+}
+
+impl TransformSnapshots {
+    pub fn publish(&mut self, snapshot: Snapshot) {
+        self.previous = mem::replace(&mut self.current, Arc::new(snapshot));
+    }
+
+    pub fn previous(&self) -> Arc<Snapshot> {
+        self.previous.clone()
+    }
+
+    pub fn current(&self) -> Arc<Snapshot> {
+        self.current.clone()
+    }
+
+    // How far to blend from the previous towards the current snapshot at the given moment. The
+    // blend lags one tick behind, so that it never has to extrapolate.
+    pub fn alpha(&self, now: Instant) -> f32 {
+        let span = (self.current.time - self.previous.time).as_secs_f32();
+        if span <= 0.0 {
+            return 1.0;
+        }
+
+        let since = now.saturating_duration_since(self.current.wall_time).as_secs_f32() * self.current.time_scale;
+        (since / span).min(1.0)
+    }
+
+    pub fn interpolate(&self, id: EntityId, alpha: f32) -> Option<Transform> {
+        match (self.previous.transforms.get(&id), self.current.transforms.get(&id)) {
+            (Some(previous), Some(current)) => Some(previous.lerp(current, alpha)),
+            (None, Some(current)) => Some(current.clone()),
+            _ => None
+        }
+    }
+}
+
+pub struct SnapshotSystem {
+    models: MemberList<&'static GraphicsModel>,
+    snapshots: Option<ResourceManager<TransformSnapshots>>,
+    time: Option<ResourceManager<SimulationTime>>,
+    clock: Option<ResourceManager<SimulationClock>>
+}
+
+impl<'a> System<'a> for SnapshotSystem {
+    type Environment = (&'a MemberList<&'static GraphicsModel>, &'a ResourceManager<TransformSnapshots>, &'a ResourceManager<SimulationTime>);
+
+    fn new() -> Self {
+        Self {
+            models: MemberList::new(),
+            snapshots: None,
+            time: None,
+            clock: None
+        }
+    }
+
+    fn stage() -> Stage {
+        Stage::RenderPrep
+    }
+
+    fn access() -> SystemAccess {
+        SystemAccess::new()
+            .read::<GraphicsModel>()
+            .read::<SimulationTime>()
+            .read::<SimulationClock>()
+            .write::<TransformSnapshots>()
+    }
+
+    fn on_fetch<T: EntityContainer>(&mut self, source: &T, resources: &Resources) -> Result<(), SystemRuntimeError> {
+        self.snapshots = resources.fetch::<TransformSnapshots>();
+        self.time = resources.fetch::<SimulationTime>();
+        self.clock = resources.fetch::<SimulationClock>();
+        self.models.fetch(source);
+
+        Result::Ok(())
+    }
+
+    fn on_event(&mut self, event: &WorldEvent) -> bool {
+        self.models.apply(event)
+    }
+
+    fn on_freeze(&'a self) -> Result<Self::Environment, SystemRuntimeError> {
+        match (&self.snapshots, &self.time) {
+            (Some(snapshots), Some(time)) => Result::Ok((&self.models, snapshots, time)),
+            (None, _) => Result::Err(SystemRuntimeError::new("Missing TransformSnapshots resource")),
+            (_, None) => Result::Err(SystemRuntimeError::new("Missing SimulationTime resource"))
+        }
+    }
+
+    fn on_run(&self, (models, snapshots, time): Self::Environment, _delta: Duration) {
+        let time = *time.lock_resource_for_read();
+        let time_scale = self.clock
+            .as_ref()
+            .and_then(|clock| clock.peek(|clock| clock.time_scale()))
+            .unwrap_or(1.0);
+
+        let transforms = models
+            .iter()
+            .map(|item| (item.entity.id(), item.lock().transform.clone()))
+            .collect();
+
+        snapshots.lock_resource_for_write().publish(Snapshot {
+            // SimulationTime is only advanced once the tick is over:
+            time: time.elapsed + time.timestep,
+            wall_time: time.wall_time,
+            time_scale,
+            transforms
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::entity::Entity;
+    use crate::world::entity::builder::EntityBuilder;
+    use crate::world::entity::registry::EntityRegistry;
+    use cgmath::Vector3;
+
+    fn ids(n: usize) -> (Vec<EntityId>, Entity, EntityRegistry) {
+        let root = EntityBuilder::new().with_children((0..n).map(|_| EntityBuilder::new()).collect()).build();
+        let registry = EntityRegistry::new();
+        registry.adopt(&root);
+
+        (root.query_direct_children().map(|entity| entity.id()).collect(), root, registry)
+    }
+
+    fn snapshot(millis: u64, wall_time: Instant, time_scale: f32, transforms: Vec<(EntityId, Transform)>) -> Snapshot {
+        Snapshot { time: Duration::from_millis(millis), wall_time, time_scale, transforms: transforms.into_iter().collect() }
+    }
+
+    fn transform(position: f32, rotation: f32, scale: f32) -> Transform {
+        let mut transform = Transform::new().with_position(Vector3::new(position, -position, 0.0));
+        transform.angular_rotation = Vector3::new(rotation, 0.0, 0.0);
+        transform.scale = Vector3::new(scale, scale, scale);
+        transform
+    }
+
+    #[test]
+    fn interpolation_blends_from_previous_to_current() {
+        let (ids, _root, _registry) = ids(3);
+        let (moving, spawned, despawned) = (ids[0], ids[1], ids[2]);
+        let now = Instant::now();
+
+        let mut snapshots = TransformSnapshots::default();
+        snapshots.publish(snapshot(10, now, 1.0, vec![(moving, transform(0.0, 0.0, 1.0)), (despawned, transform(1.0, 1.0, 1.0))]));
+        snapshots.publish(snapshot(20, now, 1.0, vec![(moving, transform(2.0, 1.0, 3.0)), (spawned, transform(5.0, 0.0, 1.0))]));
+
+        let at = |alpha: f32| {
+            let blended = snapshots.interpolate(moving, alpha).unwrap();
+            (blended.position, blended.angular_rotation.x, blended.scale.x)
+        };
+
+        assert_eq!(at(0.0), (Vector3::new(0.0, 0.0, 0.0), 0.0, 1.0));
+        assert_eq!(at(0.5), (Vector3::new(1.0, -1.0, 0.0), 0.5, 2.0));
+        assert_eq!(at(1.0), (Vector3::new(2.0, -2.0, 0.0), 1.0, 3.0));
+
+        // Entities that are new show up where they are now, and ones that are gone don't show:
+        assert_eq!(snapshots.interpolate(spawned, 0.0).unwrap().position, Vector3::new(5.0, -5.0, 0.0));
+        assert!(snapshots.interpolate(despawned, 0.5).is_none());
+    }
+
+    #[test]
+    fn alpha_follows_real_time_and_never_extrapolates() {
+        let now = Instant::now();
+        let mut snapshots = TransformSnapshots::default();
+        assert_eq!(snapshots.alpha(now), 1.0);
+
+        snapshots.publish(snapshot(10, now, 1.0, Vec::new()));
+        snapshots.publish(snapshot(20, now, 1.0, Vec::new()));
+        assert_eq!(snapshots.alpha(now), 0.0);
+        assert!((snapshots.alpha(now + Duration::from_millis(5)) - 0.5).abs() < 0.001);
+        assert_eq!(snapshots.alpha(now + Duration::from_secs(1)), 1.0);
+
+        // Twice as fast, the next tick is due in half the time:
+        snapshots.publish(snapshot(30, now, 2.0, Vec::new()));
+        assert!((snapshots.alpha(now + Duration::from_millis(2)) - 0.4).abs() < 0.001);
+    }
+}
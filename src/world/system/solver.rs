@@ -0,0 +1,415 @@
+use crate::world::system::{System, SystemRuntimeError, Stage, SystemId, SystemAccess};
+use crate::world::system::collision::{CollisionSystem, Contacts, ContactManifold};
+use crate::world::system::members::MemberList;
+use crate::world::resource::{Resources, ResourceManager};
+use crate::world::entity::component::transform::Transform;
+use crate::world::entity::component::rigid_body::RigidBody;
+use crate::world::entity::component::collider::Collider;
+use crate::world::entity::registry::EntityId;
+use crate::world::entity::event::WorldEvent;
+use crate::world::entity::EntityContainer;
+use std::collections::HashMap;
+use std::time::Duration;
+use cgmath::{Vector3, InnerSpace};
+
+// World resource, so that the solver can be tuned at runtime:
+pub struct SolverSettings {
+    // Velocity passes over all contacts per tick; more converge better on stacks of bodies:
+    pub iterations: u32,
+    // The share of the penetration that is pushed out per tick:
+    pub correction: f32,
+    // Penetration that is tolerated, so that resting bodies don't jitter:
+    pub slop: f32,
+    // Bodies approaching slower than this don't bounce:
+    pub restitution_threshold: f32
+}
+
+impl Default for SolverSettings {
+    fn default() -> Self {
+        SolverSettings {
+            iterations: 8,
+            correction: 0.4,
+            slop: 0.01,
+            restitution_threshold: 0.5
+        }
+    }
+}
+
+// The part of a rigid body the solver works on, copied out so that no body stays locked:
+struct Body {
+    position: Vector3<f32>,
+    velocity: Vector3<f32>,
+    angular_velocity: Vector3<f32>,
+    correction: Vector3<f32>,
+    inverse_mass: f32,
+    inverse_inertia: f32,
+    restitution: f32,
+    friction: f32
+}
+
+impl Body {
+    fn velocity_at(&self, r: Vector3<f32>) -> Vector3<f32> {
+        self.velocity + self.angular_velocity.cross(r)
+    }
+
+    fn apply_impulse(&mut self, impulse: Vector3<f32>, r: Vector3<f32>) {
+        self.velocity += impulse * self.inverse_mass;
+        self.angular_velocity += r.cross(impulse) * self.inverse_inertia;
+    }
+}
+
+// A single contact point, with the impulses accumulated over the iterations so far:
+struct Constraint {
+    a: Option<usize>,
+    b: Option<usize>,
+    normal: Vector3<f32>,
+    depth: f32,
+    r_a: Vector3<f32>,
+    r_b: Vector3<f32>,
+    // The normal velocity to end up with, which makes bodies bounce:
+    bounce: f32,
+    friction: f32,
+    normal_impulse: f32,
+    tangent_impulse: Vector3<f32>
+}
+
+pub struct SolverSystem {
+    rigid_bodies: MemberList<&'static mut RigidBody>,
+    contacts: Option<ResourceManager<Contacts>>,
+    settings: Option<ResourceManager<SolverSettings>>
+}
+
+impl<'a> System<'a> for SolverSystem {
+    type Environment = (&'a MemberList<&'static mut RigidBody>, &'a ResourceManager<Contacts>, &'a ResourceManager<SolverSettings>);
+
+    fn new() -> Self {
+        Self {
+            rigid_bodies: MemberList::new(),
+            contacts: None,
+            settings: None
+        }
+    }
+
+    fn stage() -> Stage {
+        Stage::Simulate
+    }
+
+    fn after() -> Vec<SystemId> {
+        vec![SystemId::of::<CollisionSystem>()]
+    }
+
+    fn access() -> SystemAccess {
+        SystemAccess::new()
+            .read::<Contacts>()
+            .read::<SolverSettings>()
+            .read::<Collider>()
+            .write::<RigidBody>()
+            .write::<Transform>()
+    }
+
+    fn on_fetch<T: EntityContainer>(&mut self, source: &T, resources: &Resources) -> Result<(), SystemRuntimeError> {
+        self.contacts = resources.fetch::<Contacts>();
+        self.settings = resources.fetch::<SolverSettings>();
+        self.rigid_bodies.fetch(source);
+        Result::Ok(())
+    }
+
+    fn on_event(&mut self, event: &WorldEvent) -> bool {
+        self.rigid_bodies.apply(event)
+    }
+
+    fn on_freeze(&'a self) -> Result<Self::Environment, SystemRuntimeError> {
+        match (&self.contacts, &self.settings) {
+            (Some(ref contacts), Some(ref settings)) => Result::Ok((&self.rigid_bodies, contacts, settings)),
+            (None, _) => Result::Err(SystemRuntimeError::new("Missing Contacts resource")),
+            (_, None) => Result::Err(SystemRuntimeError::new("Missing SolverSettings resource"))
+        }
+    }
+
+    fn on_run(&self, (rigid_bodies, contacts, settings): Self::Environment, _delta: Duration) {
+        let contacts = contacts.lock_resource_for_read();
+        let settings = settings.lock_resource_for_read();
+
+        if contacts.is_empty() {
+            return;
+        }
+
+        // Copy out every body involved in a contact, in the order they first appear:
+        let mut ids: Vec<EntityId> = Vec::new();
+        let mut indices: HashMap<EntityId, usize> = HashMap::new();
+        let mut bodies: Vec<Body> = Vec::new();
+
+        for manifold in contacts.manifolds() {
+            for id in [manifold.body_a, manifold.body_b].iter().filter_map(|id| *id) {
+                if indices.contains_key(&id) {
+                    continue;
+                }
+
+                let item = match rigid_bodies.get(id) {
+                    Some(item) => item,
+                    None => continue
+                };
+
+                let body = {
+                    let transform = item.entity.world_transform();
+                    let collider = item.entity.component::<Collider>();
+                    let collider = collider.as_ref().map(|mgr| mgr.lock_component_for_read());
+                    let rigid_body = item.lock();
+
+                    // Like the integrator, the solver only moves bodies with a transform of their
+                    // own. Any others are as good as static:
+                    let movable = item.entity.component::<Transform>().is_some();
+
+                    Body {
+                        position: transform.position,
+                        velocity: rigid_body.velocity,
+                        angular_velocity: rigid_body.angular_velocity,
+                        correction: Vector3::new(0.0, 0.0, 0.0),
+                        inverse_mass: if movable { rigid_body.inverse_mass() } else { 0.0 },
+                        inverse_inertia: if movable { rigid_body.inverse_inertia(collider.as_deref(), transform.scale) } else { 0.0 },
+                        restitution: rigid_body.restitution,
+                        friction: rigid_body.friction
+                    }
+                };
+
+                indices.insert(id, bodies.len());
+                ids.push(id);
+                bodies.push(body);
+            }
+        }
+
+        let mut constraints: Vec<Constraint> = contacts
+            .manifolds()
+            .iter()
+            .flat_map(|manifold| constraints_of(manifold, &indices, &bodies, &settings))
+            .collect();
+
+        for _ in 0..settings.iterations {
+            for constraint in constraints.iter_mut() {
+                solve(constraint, &mut bodies);
+            }
+        }
+
+        // Push apart whatever still overlaps, weighted by inverse mass like the impulses are:
+        for constraint in constraints.iter() {
+            let (inverse_mass_a, inverse_mass_b) = (
+                constraint.a.map_or(0.0, |a| bodies[a].inverse_mass),
+                constraint.b.map_or(0.0, |b| bodies[b].inverse_mass)
+            );
+
+            let inverse_mass_sum = inverse_mass_a + inverse_mass_b;
+            if inverse_mass_sum <= 0.0 {
+                continue;
+            }
+
+            let correction = constraint.normal *
+                ((constraint.depth - settings.slop).max(0.0) * settings.correction / inverse_mass_sum);
+
+            if let Some(a) = constraint.a { bodies[a].correction -= correction * inverse_mass_a; }
+            if let Some(b) = constraint.b { bodies[b].correction += correction * inverse_mass_b; }
+        }
+
+        for (id, body) in ids.iter().zip(bodies.iter()) {
+            if body.inverse_mass <= 0.0 && body.inverse_inertia <= 0.0 {
+                continue;
+            }
+
+            let item = match rigid_bodies.get(*id) {
+                Some(item) => item,
+                None => continue
+            };
+
+            {
+                let mut rigid_body = item.lock();
+                rigid_body.velocity = body.velocity;
+                rigid_body.angular_velocity = body.angular_velocity;
+            }
+
+            // Positions add up along the hierarchy, so moving the local position moves the body.
+            // Only bodies with a transform got this far:
+            if let Some(mgr) = item.entity.component::<Transform>() {
+                mgr.lock_component_for_write().position += body.correction;
+            }
+        }
+    }
+}
+
+fn constraints_of(manifold: &ContactManifold, indices: &HashMap<EntityId, usize>, bodies: &[Body], settings: &SolverSettings) -> Vec<Constraint> {
+    let a = manifold.body_a.and_then(|id| indices.get(&id).cloned());
+    let b = manifold.body_b.and_then(|id| indices.get(&id).cloned());
+
+    // Static colliders have no body, and act like one that can't move:
+    let (restitution_a, friction_a) = a.map_or((0.0, 1.0), |a| (bodies[a].restitution, bodies[a].friction));
+    let (restitution_b, friction_b) = b.map_or((0.0, 1.0), |b| (bodies[b].restitution, bodies[b].friction));
+    let restitution = restitution_a.max(restitution_b);
+    let friction = (friction_a * friction_b).sqrt();
+
+    manifold.points
+        .iter()
+        .map(|point| {
+            let r_a = a.map_or(Vector3::new(0.0, 0.0, 0.0), |a| point.position - bodies[a].position);
+            let r_b = b.map_or(Vector3::new(0.0, 0.0, 0.0), |b| point.position - bodies[b].position);
+
+            let approach_velocity = relative_velocity(a, b, r_a, r_b, bodies).dot(manifold.normal);
+            let bounce = if -approach_velocity > settings.restitution_threshold { -restitution * approach_velocity } else { 0.0 };
+
+            Constraint {
+                a,
+                b,
+                normal: manifold.normal,
+                depth: point.depth,
+                r_a,
+                r_b,
+                bounce,
+                friction,
+                normal_impulse: 0.0,
+                tangent_impulse: Vector3::new(0.0, 0.0, 0.0)
+            }
+        })
+        .collect()
+}
+
+// The velocity of b's contact point relative to a's:
+fn relative_velocity(a: Option<usize>, b: Option<usize>, r_a: Vector3<f32>, r_b: Vector3<f32>, bodies: &[Body]) -> Vector3<f32> {
+    let velocity_a = a.map_or(Vector3::new(0.0, 0.0, 0.0), |a| bodies[a].velocity_at(r_a));
+    let velocity_b = b.map_or(Vector3::new(0.0, 0.0, 0.0), |b| bodies[b].velocity_at(r_b));
+
+    velocity_b - velocity_a
+}
+
+// How much an impulse along the direction changes the relative velocity along it:
+fn effective_inverse_mass(constraint: &Constraint, direction: Vector3<f32>, bodies: &[Body]) -> f32 {
+    let term = |body: Option<usize>, r: Vector3<f32>| body.map_or(0.0, |body| {
+        bodies[body].inverse_mass + bodies[body].inverse_inertia * r.cross(direction).magnitude2()
+    });
+
+    term(constraint.a, constraint.r_a) + term(constraint.b, constraint.r_b)
+}
+
+fn apply(constraint: &Constraint, impulse: Vector3<f32>, bodies: &mut [Body]) {
+    if let Some(a) = constraint.a { bodies[a].apply_impulse(-impulse, constraint.r_a); }
+    if let Some(b) = constraint.b { bodies[b].apply_impulse(impulse, constraint.r_b); }
+}
+
+// One pass of sequential impulses. The accumulated impulses are clamped rather than each single
+// one, so that a pass may take back what an earlier one overdid.
+fn solve(constraint: &mut Constraint, bodies: &mut [Body]) {
+    let normal = constraint.normal;
+
+    let normal_mass = effective_inverse_mass(constraint, normal, bodies);
+    if normal_mass <= 0.0 {
+        return;
+    }
+
+    let velocity = relative_velocity(constraint.a, constraint.b, constraint.r_a, constraint.r_b, bodies);
+    let delta = (constraint.bounce - velocity.dot(normal)) / normal_mass;
+    let normal_impulse = (constraint.normal_impulse + delta).max(0.0);
+    apply(constraint, normal * (normal_impulse - constraint.normal_impulse), bodies);
+    constraint.normal_impulse = normal_impulse;
+
+    // Friction works against sliding, bounded by how hard the bodies are pressed together:
+    let velocity = relative_velocity(constraint.a, constraint.b, constraint.r_a, constraint.r_b, bodies);
+    let sliding = velocity - normal * velocity.dot(normal);
+    if sliding.magnitude2() < 0.000001 {
+        return;
+    }
+
+    let tangent = sliding.normalize();
+    let tangent_mass = effective_inverse_mass(constraint, tangent, bodies);
+    if tangent_mass <= 0.0 {
+        return;
+    }
+
+    let mut tangent_impulse = constraint.tangent_impulse - tangent * (sliding.magnitude() / tangent_mass);
+    let max_impulse = constraint.friction * constraint.normal_impulse;
+    if tangent_impulse.magnitude() > max_impulse {
+        tangent_impulse = tangent_impulse.normalize_to(max_impulse);
+    }
+
+    apply(constraint, tangent_impulse - constraint.tangent_impulse, bodies);
+    constraint.tangent_impulse = tangent_impulse;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::system::scheduler::Scheduler;
+    use crate::world::entity::Entity;
+    use crate::world::entity::builder::EntityBuilder;
+    use crate::world::entity::registry::EntityRegistry;
+
+    // Runs collision detection and the solver once over the given entities:
+    fn solve_once(children: Vec<EntityBuilder>) -> Entity {
+        let root = EntityBuilder::new().with_children(children).build();
+        let registry = EntityRegistry::new();
+        registry.adopt(&root);
+
+        let resources = Resources::new();
+        resources.insert(Contacts::default());
+        resources.insert(SolverSettings::default());
+
+        let mut scheduler = Scheduler::new(root.clone(), resources, Duration::from_millis(10));
+        scheduler.register::<CollisionSystem>();
+        scheduler.register::<SolverSystem>();
+        scheduler.schedule().expect("Couldn't schedule systems!");
+        scheduler.tick().expect("Couldn't run systems!");
+
+        root
+    }
+
+    fn ball(name: &'static str, x: f32, velocity: f32) -> EntityBuilder {
+        let mut body = RigidBody::new(1.0);
+        body.velocity = Vector3::new(velocity, 0.0, 0.0);
+
+        EntityBuilder::new()
+            .with_name(name)
+            .with_component(Transform::new().with_position(Vector3::new(x, 0.0, 0.0)))
+            .with_component(body)
+            .with_component(Collider::sphere(1.0))
+    }
+
+    fn state(root: &Entity, path: &str) -> (f32, f32) {
+        let entity = root.query_path(path).pop().unwrap();
+        let velocity = entity.component::<RigidBody>().unwrap().lock_component_for_read().velocity.x;
+
+        (entity.world_transform().position.x, velocity)
+    }
+
+    #[test]
+    fn head_on_collisions_push_bodies_apart() {
+        let root = solve_once(vec![ball("left", -0.9, 2.0), ball("right", 0.9, -2.0)]);
+
+        let (left_position, left_velocity) = state(&root, "left");
+        let (right_position, right_velocity) = state(&root, "right");
+
+        // The normal points from left to right, so the bodies no longer approach each other:
+        assert!(right_velocity - left_velocity >= 0.0, "{} {}", left_velocity, right_velocity);
+        assert!(right_position - left_position > 1.8);
+        // Equal masses share the impulse and the correction equally:
+        assert!((left_velocity + right_velocity).abs() < 0.0001);
+        assert!((left_position + right_position).abs() < 0.0001);
+    }
+
+    #[test]
+    fn bodies_without_a_transform_of_their_own_stay_static() {
+        let mut anchored = RigidBody::new(1.0);
+        anchored.velocity = Vector3::new(-1.0, 0.0, 0.0);
+
+        let root = solve_once(vec![
+            ball("ball", -0.9, 2.0),
+            EntityBuilder::new()
+                .with_name("anchor")
+                .with_component(Transform::new().with_position(Vector3::new(0.9, 0.0, 0.0)))
+                .with_child(EntityBuilder::new()
+                    .with_name("anchored")
+                    .with_component(anchored)
+                    .with_component(Collider::sphere(1.0)))
+        ]);
+
+        assert_eq!(state(&root, "anchor/anchored"), (0.9, -1.0));
+
+        let (position, velocity) = state(&root, "ball");
+        assert!(velocity <= -1.0, "{}", velocity);
+        assert!(position < -0.9);
+    }
+}
@@ -1,9 +1,11 @@
-use crate::world::system::{System, SysEnvComponentMut, SystemRuntimeError, SysEnvComponent};
+use crate::world::system::{System, SysEnvComponentMut, SystemRuntimeError, SysEnvComponent, Stage, SystemAccess};
+use crate::world::resource::Resources;
 use crate::world::entity::component::{Component, ComponentManager, ComponentWriteAccess};
 use crate::world::entity::component::transform::Transform;
 use crate::world::entity::component::rigid_body::RigidBody;
 use crate::world::entity::component::model::GraphicsModel;
 use crate::world::entity::{Entity, EntityContainer};
+use crate::world::entity::event::WorldEvent;
 use std::time::Duration;
 
 pub struct TranslateSystem {
@@ -17,47 +19,56 @@ impl<'a> System<'a> for TranslateSystem {
         Self { root: None }
     }
 
-    fn on_fetch<T: EntityContainer>(&mut self, source: &T) -> Result<(), SystemRuntimeError>{
+    // Propagates the transforms that were integrated during the simulate stage:
+    fn stage() -> Stage {
+        Stage::PostSimulate
+    }
+
+    fn access() -> SystemAccess {
+        SystemAccess::new()
+            .read::<Transform>()
+            .write::<GraphicsModel>()
+            .write::<RigidBody>()
+    }
+
+    fn on_fetch<T: EntityContainer>(&mut self, source: &T, _resources: &Resources) -> Result<(), SystemRuntimeError>{
         self.root = Some(source.clone().into());
         Result::Ok(())
     }
 
+    // The hierarchy is walked from the root on every run, so there is nothing to patch:
+    fn on_event(&mut self, _event: &WorldEvent) -> bool {
+        self.root.is_some()
+    }
+
     fn on_freeze(&'a self) -> Result<Self::Environment, SystemRuntimeError> {
         Result::Ok(())
     }
 
     fn on_run(&self, _: Self::Environment, _: Duration) {
-        if let Some(ref parent) = self.root {
-            let mut acc_offsets = Vec::new();
-            TranslateSystem::translate(parent, &mut acc_offsets);
-        }
-    }
-}
+        if let Some(ref root) = self.root {
+            // The accumulated (absolute) transform of the entity last visited at each depth:
+            let mut acc_offsets: Vec<Transform> = Vec::new();
 
-impl TranslateSystem {
-    fn translate(parent: &Entity, acc_offsets: &mut Vec<Transform>) {
-        if let Some(mgr) = parent.component::<Transform>() {
-            acc_offsets.push((*mgr.lock_component_for_write()).clone());
-        }
+            for visit in root.traverse().iter() {
+                acc_offsets.truncate(visit.depth);
 
-        let (graphics_model, rigid_body) = (parent.component::<GraphicsModel>(), parent.component::<RigidBody>());
-        if graphics_model.is_some() || rigid_body.is_some() {
-            let absolute_transform = acc_offsets.iter().fold(Transform::new(), |acc, x| acc.with_offset(x));
+                let parent_transform = acc_offsets.last().cloned().unwrap_or_else(Transform::new);
+                let absolute_transform = match visit.entity.component::<Transform>() {
+                    Some(mgr) => parent_transform.with_offset(&*mgr.lock_component_for_read()),
+                    None => parent_transform
+                };
 
-            if let Some(mgr) = graphics_model {
-                (*mgr.lock_component_for_write()).view.translate(&absolute_transform);
-            }
+                if let Some(mgr) = visit.entity.component::<GraphicsModel>() {
+                    (*mgr.lock_component_for_write()).transform = absolute_transform.clone();
+                }
 
-            if let Some(mgr) = rigid_body {
-                (*mgr.lock_component_for_write()).last_absolute_position = absolute_transform.position;
-            }
-        }
+                if let Some(mgr) = visit.entity.component::<RigidBody>() {
+                    (*mgr.lock_component_for_write()).last_absolute_position = absolute_transform.position;
+                }
 
-        let mut iter = parent.query_direct_children();
-        while let Some(child) = iter.next() {
-            TranslateSystem::translate(&child, acc_offsets);
+                acc_offsets.push(absolute_transform);
+            }
         }
-
-        acc_offsets.pop();
     }
-}
\ No newline at end of file
+}